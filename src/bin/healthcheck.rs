@@ -0,0 +1,145 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const DEFAULT_CONFIG_LOCATION: &str = "config/default.toml";
+const HEALTHCHECK_PATH: &str = "/v1/liveness";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn read_server_section(config_file: &str) -> Result<toml::Value, String> {
+    let context = std::fs::read_to_string(config_file)
+        .map_err(|e| format!("Read configure file {} error: {}", config_file, e))?;
+    let config = context
+        .parse::<toml::Value>()
+        .map_err(|e| format!("Decode configure file {} error: {}", config_file, e))?;
+    config
+        .get("server")
+        .cloned()
+        .ok_or_else(|| format!("Section [server] not found in {}", config_file))
+}
+
+fn read_server_address(server: &toml::Value, config_file: &str) -> Result<(String, u16), String> {
+    let addr = server
+        .get("addr")
+        .and_then(|addr| addr.as_str())
+        .unwrap_or("127.0.0.1")
+        .to_string();
+    let port = server
+        .get("port")
+        .and_then(|port| port.as_integer())
+        .and_then(|port| u16::try_from(port).ok())
+        .ok_or_else(|| format!("Invalid server.port in {}", config_file))?;
+    Ok((addr, port))
+}
+
+// Probes are mounted under `base_path` unless they are also served without prefix
+fn read_probe_path(server: &toml::Value) -> String {
+    let exclude_probes = server
+        .get("base_path_exclude_probes")
+        .and_then(|exclude| exclude.as_bool())
+        .unwrap_or(false);
+    match server.get("base_path").and_then(|path| path.as_str()) {
+        Some(base_path) if !base_path.is_empty() && !exclude_probes => {
+            format!("{}{}", base_path, HEALTHCHECK_PATH)
+        }
+        _ => HEALTHCHECK_PATH.to_string(),
+    }
+}
+
+fn resolve_target() -> Result<(SocketAddr, String), String> {
+    let config_file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CONFIG_LOCATION.to_string());
+    let env_addr = std::env::var("STATUS_UPSTREAM_ADDR").ok();
+    let env_port = std::env::var("STATUS_UPSTREAM_PORT").ok();
+    let (addr, port, path) = match (env_addr, env_port) {
+        // Configure file is optional when address is given by environment
+        (Some(addr), Some(port)) => (
+            addr,
+            port,
+            read_server_section(&config_file)
+                .map(|server| read_probe_path(&server))
+                .unwrap_or_else(|_| HEALTHCHECK_PATH.to_string()),
+        ),
+        (env_addr, env_port) => {
+            let server = read_server_section(&config_file)?;
+            let (addr, port) = read_server_address(&server, &config_file)?;
+            (
+                env_addr.unwrap_or(addr),
+                env_port.unwrap_or_else(|| port.to_string()),
+                read_probe_path(&server),
+            )
+        }
+    };
+    // Server listening on unspecified address is reachable from loopback
+    let addr = match addr.as_str() {
+        "0.0.0.0" => "127.0.0.1".to_string(),
+        "::" | "[::]" => "::1".to_string(),
+        _ => addr,
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|e| format!("Invalid port {:?}: {}", port, e))?;
+    let target = (addr.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Resolve {}:{} error: {}", addr, port, e))?
+        .next()
+        .ok_or_else(|| format!("Resolve {}:{} got no address", addr, port))?;
+    Ok((target, path))
+}
+
+fn check(target: SocketAddr, path: &str) -> Result<(), String> {
+    let mut stream = TcpStream::connect_timeout(&target, TIMEOUT)
+        .map_err(|e| format!("Connect to {} error: {}", target, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok();
+    stream.set_write_timeout(Some(TIMEOUT)).ok();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, target
+    )
+    .map_err(|e| format!("Send request to {} error: {}", target, e))?;
+
+    let mut buffer = [0u8; 64];
+    let mut length = 0;
+    while length < buffer.len() && !buffer[..length].contains(&b'\n') {
+        match stream.read(&mut buffer[length..]) {
+            Ok(0) => break,
+            Ok(size) => length += size,
+            Err(e) => return Err(format!("Read response from {} error: {}", target, e)),
+        }
+    }
+    let response = String::from_utf8_lossy(&buffer[..length]);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(format!(
+            "Unexpected response from {}{}: {:?}",
+            target, path, status_line
+        )),
+    }
+}
+
+fn main() {
+    if let Err(e) = resolve_target().and_then(|(target, path)| check(target, &path)) {
+        eprintln!("Healthcheck failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -15,23 +15,57 @@
  ** along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::datastructures::ServerLastStatus;
 use crate::web_service::current::FetchReturnType;
 use crate::DEFAULT_DATABASE_LOCATION;
+use anyhow::anyhow;
+use chrono::DateTime;
 #[cfg(any(feature = "env_logger", feature = "log4rs"))]
 use log::error;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 #[cfg(feature = "spdlog-rs")]
 use spdlog::prelude::*;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path::Path;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+const DEFAULT_UPSTREAM_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_AUDIT_RETENTION_DAYS: u64 = 90;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub struct ServerConfig {
     addr: String,
     port: u16,
     auth_header: Option<String>,
+    /// Value of `Authorization` header required by /admin and reset endpoints, disabled if empty
+    admin_auth_header: Option<String>,
+    /// Serve a human readable status page at /status
     public_status_page: bool,
     database_location: Option<String>,
+    /// Timeout of each request to upstream (in seconds)
+    upstream_timeout_secs: Option<u64>,
+    /// Number of tokio worker threads, single thread if 0 or absent
+    worker_threads: Option<usize>,
+    /// Days to keep status change audit log
+    audit_retention_days: Option<u64>,
+    /// Custom status aliases reported by agents (alias = "status")
+    status_aliases: Option<HashMap<String, String>>,
+    /// Refuse to start if more components than this are served (after `--tags` filter)
+    max_components: Option<usize>,
+    /// Mount all routes under this path prefix (e.g. "/monitor") for reverse proxy
+    base_path: Option<String>,
+    /// Also serve /v1/liveness and /v1/readiness without `base_path` prefix
+    base_path_exclude_probes: Option<bool>,
+    /// Log every request through tower-http TraceLayer
+    enable_request_logging: Option<bool>,
+    /// Add security headers to all responses
+    #[serde(default)]
+    security_headers: SecurityHeadersConfig,
+    #[cfg(feature = "grpc")]
+    grpc_port: Option<u16>,
 }
 
 impl ServerConfig {
@@ -47,6 +81,11 @@ impl ServerConfig {
             Some(ref auth) => auth.clone(),
         }
     }
+    pub fn admin_auth_header(&self) -> Option<&str> {
+        self.admin_auth_header
+            .as_deref()
+            .filter(|header| !header.is_empty())
+    }
     pub fn public_status_page(&self) -> bool {
         self.public_status_page
     }
@@ -56,11 +95,100 @@ impl ServerConfig {
             Some(ref location) => location.clone(),
         }
     }
+    pub fn upstream_timeout_secs(&self) -> u64 {
+        self.upstream_timeout_secs
+            .unwrap_or(DEFAULT_UPSTREAM_TIMEOUT_SECS)
+    }
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads
+    }
+    pub fn audit_retention_days(&self) -> u64 {
+        self.audit_retention_days
+            .unwrap_or(DEFAULT_AUDIT_RETENTION_DAYS)
+    }
+    pub fn status_aliases(&self) -> Option<&HashMap<String, String>> {
+        self.status_aliases.as_ref()
+    }
+    pub fn max_components(&self) -> Option<usize> {
+        self.max_components
+    }
+    pub fn base_path(&self) -> Option<&str> {
+        self.base_path.as_deref().filter(|path| !path.is_empty())
+    }
+    pub fn base_path_exclude_probes(&self) -> bool {
+        self.base_path_exclude_probes.unwrap_or(false)
+    }
+    pub fn enable_request_logging(&self) -> bool {
+        self.enable_request_logging.unwrap_or(true)
+    }
+    pub fn security_headers(&self) -> &SecurityHeadersConfig {
+        &self.security_headers
+    }
+    #[cfg(feature = "grpc")]
+    pub fn grpc_port(&self) -> Option<u16> {
+        self.grpc_port
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub struct SecurityHeadersConfig {
+    enabled: Option<bool>,
+    /// Disable if server is not served over HTTPS
+    strict_transport_security: Option<bool>,
+}
+
+impl SecurityHeadersConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+    pub fn strict_transport_security(&self) -> bool {
+        self.strict_transport_security.unwrap_or(true)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConfigDiff {
+    added_components: Vec<Component>,
+    removed_components: Vec<Component>,
+    /// Components keep uuid but change other fields, in (old, new) pair
+    changed_components: Vec<(Component, Component)>,
+    changed_oauth: bool,
+    changed_server: bool,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+impl ConfigDiff {
+    pub fn added_components(&self) -> &Vec<Component> {
+        &self.added_components
+    }
+    pub fn removed_components(&self) -> &Vec<Component> {
+        &self.removed_components
+    }
+    pub fn changed_components(&self) -> &Vec<(Component, Component)> {
+        &self.changed_components
+    }
+    pub fn changed_oauth(&self) -> bool {
+        self.changed_oauth
+    }
+    pub fn changed_server(&self) -> bool {
+        self.changed_server
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 pub struct Configure {
     statuspage: StatusPageUpstream,
+    #[cfg(feature = "pagerduty")]
+    #[serde(default)]
+    pagerduty: PagerDutyUpstream,
+    #[cfg(feature = "freshping")]
+    #[serde(default)]
+    freshping: FreshpingUpstream,
+    #[serde(default)]
+    discord: DiscordUpstream,
+    #[serde(default)]
+    matrix: MatrixUpstream,
+    #[serde(default)]
+    betterstack: BetterStackUpstream,
     components: Components,
     server: ServerConfig,
 }
@@ -76,7 +204,7 @@ impl Configure {
             );
         }
         let context = context?;
-        let cfg = match toml::from_str(context.as_str()) {
+        let mut cfg: Configure = match toml::from_str(context.as_str()) {
             Ok(cfg) => cfg,
             Err(e) => {
                 error!(
@@ -87,29 +215,250 @@ impl Configure {
                 return Err(anyhow::Error::from(e));
             }
         };
+        cfg.apply_component_map();
+        if let Err(e) = cfg.validate() {
+            error!(
+                "Got error while validate {:?}: {}",
+                path.as_ref().display(),
+                e
+            );
+            return Err(e);
+        }
         Ok(cfg)
     }
 
+    fn apply_component_map(&mut self) {
+        for component in &mut self.components.0 {
+            if let Some(component_id) = self.statuspage.component_map.get(&component.uuid) {
+                component.identity_id = component_id.clone();
+            }
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+        if self.server.port == 0 {
+            errors.push("server.port: should be between 1 and 65535".to_string());
+        }
+        if self.statuspage.enabled && self.statuspage.oauth.is_empty() {
+            errors.push("statuspage.oauth: should not be empty if statuspage enabled".to_string());
+        }
+        #[cfg(feature = "pagerduty")]
+        if self.pagerduty.enabled && self.pagerduty.routing_key.is_empty() {
+            errors.push(
+                "pagerduty.routing_key: should not be empty if pagerduty enabled".to_string(),
+            );
+        }
+        #[cfg(feature = "freshping")]
+        if self.freshping.enabled {
+            if self.freshping.subdomain.is_empty() {
+                errors.push(
+                    "freshping.subdomain: should not be empty if freshping enabled".to_string(),
+                );
+            }
+            if self.freshping.api_key.is_empty() {
+                errors.push(
+                    "freshping.api_key: should not be empty if freshping enabled".to_string(),
+                );
+            }
+        }
+        if self.discord.enabled && self.discord.webhook_url.is_empty() {
+            errors.push("discord.webhook_url: should not be empty if discord enabled".to_string());
+        }
+        if self.matrix.enabled {
+            if self.matrix.homeserver_url.is_empty() {
+                errors.push(
+                    "matrix.homeserver_url: should not be empty if matrix enabled".to_string(),
+                );
+            }
+            if self.matrix.access_token.is_empty() {
+                errors
+                    .push("matrix.access_token: should not be empty if matrix enabled".to_string());
+            }
+            if self.matrix.room_id.is_empty() {
+                errors.push("matrix.room_id: should not be empty if matrix enabled".to_string());
+            }
+        }
+        if self.betterstack.enabled
+            && self.betterstack.heartbeat_url.is_empty()
+            && self
+                .components()
+                .iter()
+                .all(|component| component.betterstack_heartbeat_url().is_none())
+        {
+            errors.push(
+                "betterstack.heartbeat_url: should not be empty if betterstack enabled and no component has betterstack_heartbeat_url".to_string(),
+            );
+        }
+        for (index, template) in self.statuspage.maintenance_templates.iter().enumerate() {
+            if let Err(e) = template.scheduled_for_timestamp() {
+                errors.push(format!(
+                    "statuspage.maintenance_templates[{}].scheduled_for: {}",
+                    index, e
+                ));
+            }
+            if let Err(e) = template.scheduled_until_timestamp() {
+                errors.push(format!(
+                    "statuspage.maintenance_templates[{}].scheduled_until: {}",
+                    index, e
+                ));
+            }
+        }
+        if let Some(base_path) = self.server.base_path() {
+            if !base_path.starts_with('/') || base_path.ends_with('/') {
+                errors.push(format!(
+                    "server.base_path: {:?} should start with '/' and not end with '/'",
+                    base_path
+                ));
+            }
+        }
+        if let Some(ref aliases) = self.server.status_aliases {
+            for (alias, status) in aliases {
+                if let Err(e) = ServerLastStatus::try_from(status.as_str()) {
+                    errors.push(format!("server.status_aliases.{}: {}", alias, e));
+                }
+            }
+        }
+        for (index, component) in self.components().iter().enumerate() {
+            if component.uuid.is_empty() {
+                errors.push(format!("components[{}].uuid: should not be empty", index));
+            } else if !is_uuid_like(&component.uuid) {
+                errors.push(format!(
+                    "components[{}].uuid: {:?} is not a valid uuid",
+                    index, component.uuid
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid configure:\n{}", errors.join("\n")))
+        }
+    }
+
+    pub fn diff(old: &Configure, new: &Configure) -> ConfigDiff {
+        let exists_in = |cfg: &Configure, uuid: &str| {
+            cfg.components()
+                .iter()
+                .any(|component| component.uuid == uuid)
+        };
+        ConfigDiff {
+            added_components: new
+                .components()
+                .iter()
+                .filter(|component| !exists_in(old, &component.uuid))
+                .cloned()
+                .collect(),
+            removed_components: old
+                .components()
+                .iter()
+                .filter(|component| !exists_in(new, &component.uuid))
+                .cloned()
+                .collect(),
+            changed_components: old
+                .components()
+                .iter()
+                .filter_map(|component| {
+                    new.components()
+                        .iter()
+                        .find(|new_component| new_component.uuid == component.uuid)
+                        .filter(|new_component| *new_component != component)
+                        .map(|new_component| (component.clone(), new_component.clone()))
+                })
+                .collect(),
+            changed_oauth: old.statuspage.oauth != new.statuspage.oauth,
+            changed_server: old.server != new.server,
+        }
+    }
+
     pub fn statuspage(&self) -> &StatusPageUpstream {
         &self.statuspage
     }
+    #[cfg(feature = "pagerduty")]
+    pub fn pagerduty(&self) -> &PagerDutyUpstream {
+        &self.pagerduty
+    }
+    #[cfg(feature = "freshping")]
+    pub fn freshping(&self) -> &FreshpingUpstream {
+        &self.freshping
+    }
+    pub fn discord(&self) -> &DiscordUpstream {
+        &self.discord
+    }
+    pub fn matrix(&self) -> &MatrixUpstream {
+        &self.matrix
+    }
+    pub fn betterstack(&self) -> &BetterStackUpstream {
+        &self.betterstack
+    }
     pub fn server(&self) -> &ServerConfig {
         &self.server
     }
 
+    pub fn retain_components_by_tags(&mut self, tags: &[String]) {
+        self.components
+            .0
+            .retain(|component| component.tags().iter().any(|tag| tags.contains(tag)));
+    }
+
+    /// Check after `--tags` filter, components filtered out are not served and not counted
+    pub fn check_component_limit(&self) -> anyhow::Result<()> {
+        match self.server.max_components() {
+            Some(max_components) if self.components().len() > max_components => Err(anyhow!(
+                "server.max_components: Component count {} exceeds maximum allowed {}",
+                self.components().len(),
+                max_components
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Map id passed to upstreams to component, used by upstreams which show component name
+    pub fn components_by_upstream_id(&self) -> HashMap<String, Component> {
+        self.components()
+            .iter()
+            .map(|component| (component.upstream_id().to_string(), component.clone()))
+            .collect()
+    }
+
     pub fn is_empty_services(&self) -> bool {
         self.components.0.is_empty()
     }
     pub fn components(&self) -> &Vec<Component> {
         &self.components.0
     }
+    pub fn components_mut(&mut self) -> &mut Vec<Component> {
+        &mut self.components.0
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+fn is_uuid_like(s: &str) -> bool {
+    let hyphen_ok = match s.len() {
+        32 => true,
+        36 => [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-'),
+        _ => false,
+    };
+    hyphen_ok
+        && s.chars()
+            .filter(|c| *c != '-')
+            .all(|c| c.is_ascii_hexdigit())
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 pub struct StatusPageUpstream {
     enabled: bool,
     #[serde(default)]
     oauth: String,
+    /// Override `identity_id` of components (uuid = "component id")
+    #[serde(default)]
+    component_map: HashMap<String, String>,
+    /// Create scheduled maintenance when `scheduled_for` arrived
+    #[serde(default)]
+    maintenance_templates: Vec<MaintenanceTemplate>,
+    /// Retry times on 429 or 5xx response
+    max_retries: Option<u32>,
+    /// Base backoff of retry (in milliseconds)
+    retry_backoff_ms: Option<u64>,
 }
 
 impl StatusPageUpstream {
@@ -120,19 +469,192 @@ impl StatusPageUpstream {
     pub fn enabled(&self) -> bool {
         self.enabled
     }
+
+    pub fn maintenance_templates(&self) -> &[MaintenanceTemplate] {
+        &self.maintenance_templates
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    pub fn retry_backoff_ms(&self) -> u64 {
+        self.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct MaintenanceTemplate {
+    name: String,
+    #[serde(default)]
+    body: String,
+    page: String,
+    scheduled_for: String,
+    scheduled_until: String,
+    #[serde(default)]
+    component_ids: Vec<String>,
+}
+
+impl MaintenanceTemplate {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+    pub fn page(&self) -> &str {
+        &self.page
+    }
+    pub fn scheduled_for(&self) -> &str {
+        &self.scheduled_for
+    }
+    pub fn scheduled_until(&self) -> &str {
+        &self.scheduled_until
+    }
+    pub fn component_ids(&self) -> &[String] {
+        &self.component_ids
+    }
+    pub fn scheduled_for_timestamp(&self) -> anyhow::Result<i64> {
+        Ok(DateTime::parse_from_rfc3339(&self.scheduled_for)?.timestamp())
+    }
+    pub fn scheduled_until_timestamp(&self) -> anyhow::Result<i64> {
+        Ok(DateTime::parse_from_rfc3339(&self.scheduled_until)?.timestamp())
+    }
+}
+
+#[cfg(feature = "pagerduty")]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct PagerDutyUpstream {
+    enabled: bool,
+    #[serde(default)]
+    routing_key: String,
+}
+
+#[cfg(feature = "pagerduty")]
+impl PagerDutyUpstream {
+    pub fn routing_key(&self) -> &str {
+        &self.routing_key
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(feature = "freshping")]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct FreshpingUpstream {
+    enabled: bool,
+    #[serde(default)]
+    subdomain: String,
+    #[serde(default)]
+    api_key: String,
+}
+
+#[cfg(feature = "freshping")]
+impl FreshpingUpstream {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn subdomain(&self) -> &str {
+        &self.subdomain
+    }
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct DiscordUpstream {
+    enabled: bool,
+    #[serde(default)]
+    webhook_url: String,
+    username: Option<String>,
+    avatar_url: Option<String>,
+}
+
+impl DiscordUpstream {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+    pub fn avatar_url(&self) -> Option<&str> {
+        self.avatar_url.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct MatrixUpstream {
+    enabled: bool,
+    #[serde(default)]
+    homeserver_url: String,
+    #[serde(default)]
+    access_token: String,
+    #[serde(default)]
+    room_id: String,
+}
+
+impl MatrixUpstream {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn homeserver_url(&self) -> &str {
+        &self.homeserver_url
+    }
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct BetterStackUpstream {
+    enabled: bool,
+    #[serde(default)]
+    heartbeat_url: String,
+    down_url: Option<String>,
+}
+
+impl BetterStackUpstream {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    /// Used by components without `betterstack_heartbeat_url`
+    pub fn heartbeat_url(&self) -> Option<&str> {
+        Some(self.heartbeat_url.as_str()).filter(|url| !url.is_empty())
+    }
+    pub fn down_url(&self) -> Option<&str> {
+        self.down_url.as_deref().filter(|url| !url.is_empty())
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 pub struct Components(Vec<Component>);
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub struct Component {
     uuid: String,
     name: String,
+    /// Statuspage component id
     #[serde(default)]
     identity_id: String,
+    /// Statuspage page id
     #[serde(default)]
     page: String,
+    /// Used by `--tags` filter
+    tags: Option<Vec<String>>,
+    /// Freshping check id, paused while component is in major outage
+    #[cfg(feature = "freshping")]
+    freshping_check_id: Option<String>,
+    /// BetterStack heartbeat url, override `betterstack.heartbeat_url`
+    betterstack_heartbeat_url: Option<String>,
 }
 
 impl Component {
@@ -150,6 +672,10 @@ impl Component {
             name,
             identity_id,
             page,
+            tags: None,
+            #[cfg(feature = "freshping")]
+            freshping_check_id: None,
+            betterstack_heartbeat_url: None,
         }
     }
 
@@ -161,9 +687,47 @@ impl Component {
         &self.name
     }
 
+    #[cfg(feature = "freshping")]
+    pub fn freshping_check_id(&self) -> Option<&str> {
+        self.freshping_check_id
+            .as_deref()
+            .filter(|check_id| !check_id.is_empty())
+    }
+
+    pub fn betterstack_heartbeat_url(&self) -> Option<&str> {
+        self.betterstack_heartbeat_url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name
+    }
+
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.uuid
+        } else {
+            &self.name
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+
     pub fn need_push(&self) -> bool {
         !self.identity_id.is_empty() && !self.page.is_empty()
     }
+
+    /// Statuspage component id if mapped, otherwise uuid
+    pub fn upstream_id(&self) -> &str {
+        if self.need_push() {
+            &self.identity_id
+        } else {
+            &self.uuid
+        }
+    }
 }
 
 impl From<FetchReturnType> for Component {
@@ -173,6 +737,166 @@ impl From<FetchReturnType> for Component {
             name: "".to_string(),
             identity_id: ret.2.unwrap_or_else(|| "".to_string()),
             page: ret.1.unwrap_or_else(|| "".to_string()),
+            tags: None,
+            #[cfg(feature = "freshping")]
+            freshping_check_id: None,
+            betterstack_heartbeat_url: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Configure;
+
+    const UUID_A: &str = "00000000000000000000000000000001";
+    const UUID_B: &str = "00000000000000000000000000000002";
+
+    fn build_configure(oauth: &str, port: u16, uuids: &[&str]) -> Configure {
+        let components = uuids
+            .iter()
+            .map(|uuid| format!("{{ uuid = \"{}\", name = \"{}\" }}", uuid, uuid))
+            .collect::<Vec<_>>()
+            .join(", ");
+        toml::from_str(&format!(
+            r#"
+            components = [{}]
+
+            [statuspage]
+            enabled = false
+            oauth = "{}"
+
+            [server]
+            addr = "127.0.0.1"
+            port = {}
+            public_status_page = false
+            "#,
+            components, oauth, port
+        ))
+        .unwrap()
+    }
+
+    fn uuids(components: &[super::Component]) -> Vec<&str> {
+        components
+            .iter()
+            .map(|component| component.uuid())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let diff = Configure::diff(&old, &old.clone());
+        assert!(diff.added_components().is_empty());
+        assert!(diff.removed_components().is_empty());
+        assert!(diff.changed_components().is_empty());
+        assert!(!diff.changed_oauth());
+        assert!(!diff.changed_server());
+    }
+
+    #[test]
+    fn test_diff_added_component() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let new = build_configure("oauth", 41132, &[UUID_A, UUID_B]);
+        let diff = Configure::diff(&old, &new);
+        assert_eq!(uuids(diff.added_components()), vec![UUID_B]);
+        assert!(diff.removed_components().is_empty());
+        assert!(!diff.changed_oauth());
+        assert!(!diff.changed_server());
+    }
+
+    #[test]
+    fn test_diff_removed_component() {
+        let old = build_configure("oauth", 41132, &[UUID_A, UUID_B]);
+        let new = build_configure("oauth", 41132, &[UUID_B]);
+        let diff = Configure::diff(&old, &new);
+        assert!(diff.added_components().is_empty());
+        assert_eq!(uuids(diff.removed_components()), vec![UUID_A]);
+        assert!(!diff.changed_oauth());
+        assert!(!diff.changed_server());
+    }
+
+    #[test]
+    fn test_diff_replaced_component() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let new = build_configure("oauth", 41132, &[UUID_B]);
+        let diff = Configure::diff(&old, &new);
+        assert_eq!(uuids(diff.added_components()), vec![UUID_B]);
+        assert_eq!(uuids(diff.removed_components()), vec![UUID_A]);
+    }
+
+    #[test]
+    fn test_diff_changed_component() {
+        let old = build_configure("oauth", 41132, &[UUID_A, UUID_B]);
+        let mut new = old.clone();
+        new.components_mut()[1].set_name("renamed".to_string());
+        let diff = Configure::diff(&old, &new);
+        assert!(diff.added_components().is_empty());
+        assert!(diff.removed_components().is_empty());
+        assert_eq!(diff.changed_components().len(), 1);
+        let (old_component, new_component) = &diff.changed_components()[0];
+        assert_eq!(old_component.uuid(), UUID_B);
+        assert_eq!(new_component.name(), "renamed");
+    }
+
+    #[test]
+    fn test_diff_changed_oauth() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let new = build_configure("new oauth", 41132, &[UUID_A]);
+        let diff = Configure::diff(&old, &new);
+        assert!(diff.added_components().is_empty());
+        assert!(diff.removed_components().is_empty());
+        assert!(diff.changed_oauth());
+        assert!(!diff.changed_server());
+    }
+
+    #[test]
+    fn test_diff_changed_server() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let new = build_configure("oauth", 41133, &[UUID_A]);
+        let diff = Configure::diff(&old, &new);
+        assert!(diff.added_components().is_empty());
+        assert!(diff.removed_components().is_empty());
+        assert!(!diff.changed_oauth());
+        assert!(diff.changed_server());
+    }
+
+    #[test]
+    fn test_diff_changed_all() {
+        let old = build_configure("oauth", 41132, &[UUID_A]);
+        let new = build_configure("new oauth", 41133, &[UUID_B]);
+        let diff = Configure::diff(&old, &new);
+        assert_eq!(uuids(diff.added_components()), vec![UUID_B]);
+        assert_eq!(uuids(diff.removed_components()), vec![UUID_A]);
+        assert!(diff.changed_oauth());
+        assert!(diff.changed_server());
+    }
+
+    #[test]
+    fn test_component_limit_after_tags_filter() {
+        let mut config: Configure = toml::from_str(&format!(
+            r#"
+            components = [
+                {{ uuid = "{}", name = "a", tags = ["a"] }},
+                {{ uuid = "{}", name = "b", tags = ["b"] }},
+            ]
+
+            [statuspage]
+            enabled = false
+            oauth = ""
+
+            [server]
+            addr = "127.0.0.1"
+            port = 41132
+            public_status_page = false
+            max_components = 1
+            "#,
+            UUID_A, UUID_B
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        assert!(config.check_component_limit().is_err());
+        config.retain_components_by_tags(&["a".to_string()]);
+        config.check_component_limit().unwrap();
+    }
+}
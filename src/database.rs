@@ -1,4 +1,18 @@
+use crate::configure::Component;
+use crate::datastructures::ServerLastStatus;
+use anyhow::anyhow;
+#[cfg(any(feature = "env_logger", feature = "log4rs"))]
+use log::{info, warn};
+use serde::Serializer;
+use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "spdlog-rs")]
+use spdlog::prelude::*;
+use sqlx::{Connection, SqliteConnection};
+use std::path::Path;
+
 pub mod v1 {
+    // Legacy schema reference, database of this version is only migrated, never created
+    #[allow(dead_code)]
     pub const CREATE_TABLE: &str = r#"CREATE TABLE "machines" (
             "uuid"	TEXT NOT NULL,
             "status"	TEXT NOT NULL,
@@ -16,6 +30,601 @@ pub mod v1 {
     pub const VERSION: &str = "1";
 }
 
+pub mod v2 {
+    pub const MIGRATE_FROM_V1: &str = r#"CREATE TABLE IF NOT EXISTS "upstream_meta" (
+            "key"	TEXT NOT NULL,
+            "value"	TEXT NOT NULL,
+            PRIMARY KEY("key")
+        );
+        INSERT OR REPLACE INTO "upstream_meta" VALUES ('version', '2');
+        "#;
+    pub const ADD_NEED_PUSH_COLUMN: &str =
+        r#"ALTER TABLE "machines" ADD COLUMN "need_push" INTEGER NOT NULL DEFAULT 0"#;
+    pub const VERSION: &str = "2";
+}
+
+pub mod v3 {
+    pub const MIGRATE_FROM_V2: &str = r#"ALTER TABLE "machines" ADD COLUMN "tags" TEXT NOT NULL DEFAULT '[]';
+        UPDATE "upstream_meta" SET "value" = '3' WHERE "key" = 'version';
+        "#;
+    pub const VERSION: &str = "3";
+}
+
+pub mod v4 {
+    // SQLite can not change column type, so rebuild "machines" table with integer status code
+    pub const MIGRATE_FROM_V3: &str = r#"CREATE TABLE "machines_v4" (
+            "uuid"	TEXT NOT NULL,
+            "status"	INTEGER NOT NULL DEFAULT 4,
+            "last_update"	INTEGER NOT NULL,
+            "need_push"	INTEGER NOT NULL DEFAULT 0,
+            "page"	TEXT,
+            "component_id"	TEXT,
+            "tags"	TEXT NOT NULL DEFAULT '[]',
+            PRIMARY KEY("uuid")
+        );
+        INSERT OR REPLACE INTO "machines_v4" ("uuid", "status", "last_update", "need_push", "page", "component_id", "tags")
+            SELECT "uuid",
+                CASE "status"
+                    WHEN 'operational' THEN 0
+                    WHEN 'degraded_performance' THEN 1
+                    WHEN 'partial_outage' THEN 2
+                    WHEN 'major_outage' THEN 3
+                    ELSE 4
+                END,
+                "last_update", "need_push", "page", "component_id", "tags"
+            FROM "machines";
+        DROP TABLE "machines";
+        ALTER TABLE "machines_v4" RENAME TO "machines";
+        UPDATE "upstream_meta" SET "value" = '4' WHERE "key" = 'version';
+        "#;
+    pub const VERSION: &str = "4";
+}
+
+pub mod v5 {
+    // Table may be created by scheduler of earlier build
+    pub const MIGRATE_FROM_V4: &str = r#"CREATE TABLE IF NOT EXISTS "maintenance_history" (
+            "name"	TEXT NOT NULL,
+            "created_at"	INTEGER NOT NULL,
+            PRIMARY KEY("name")
+        );
+        UPDATE "upstream_meta" SET "value" = '5' WHERE "key" = 'version';
+        "#;
+    pub const VERSION: &str = "5";
+}
+
+pub mod v6 {
+    pub const CREATE_TABLE: &str = r#"CREATE TABLE "machines" (
+            "uuid"	TEXT NOT NULL,
+            "status"	INTEGER NOT NULL DEFAULT 4,
+            "last_update"	INTEGER NOT NULL,
+            "need_push"	INTEGER NOT NULL DEFAULT 0,
+            "page"	TEXT,
+            "component_id"	TEXT,
+            "tags"	TEXT NOT NULL DEFAULT '[]',
+            PRIMARY KEY("uuid")
+        );
+        CREATE TABLE "upstream_meta" (
+            "key"	TEXT NOT NULL,
+            "value"	TEXT NOT NULL,
+            PRIMARY KEY("key")
+        );
+        CREATE TABLE "maintenance_history" (
+            "name"	TEXT NOT NULL,
+            "created_at"	INTEGER NOT NULL,
+            PRIMARY KEY("name")
+        );
+        CREATE TABLE "audit_log" (
+            "id"	INTEGER NOT NULL,
+            "uuid"	TEXT NOT NULL,
+            "old_status"	TEXT NOT NULL,
+            "new_status"	TEXT NOT NULL,
+            "changed_at"	INTEGER NOT NULL,
+            "source_ip"	TEXT,
+            "request_id"	TEXT,
+            PRIMARY KEY("id" AUTOINCREMENT)
+        );
+        INSERT INTO "upstream_meta" VALUES ('version', '6');
+        "#;
+    // Table may be created at startup by earlier build
+    pub const MIGRATE_FROM_V5: &str = r#"CREATE TABLE IF NOT EXISTS "audit_log" (
+            "id"	INTEGER NOT NULL,
+            "uuid"	TEXT NOT NULL,
+            "old_status"	TEXT NOT NULL,
+            "new_status"	TEXT NOT NULL,
+            "changed_at"	INTEGER NOT NULL,
+            "source_ip"	TEXT,
+            "request_id"	TEXT,
+            PRIMARY KEY("id" AUTOINCREMENT)
+        );
+        UPDATE "upstream_meta" SET "value" = '6' WHERE "key" = 'version';
+        "#;
+    pub const VERSION: &str = "6";
+}
+
+pub mod audit {
+    use serde_derive::Serialize;
+    use sqlx::SqliteConnection;
+
+    #[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+    pub struct AuditLogEntry {
+        id: i64,
+        uuid: String,
+        old_status: String,
+        new_status: String,
+        changed_at: i64,
+        source_ip: Option<String>,
+        request_id: Option<String>,
+    }
+
+    pub async fn insert(
+        conn: &mut SqliteConnection,
+        uuid: &str,
+        old_status: &str,
+        new_status: &str,
+        changed_at: u64,
+        source_ip: Option<String>,
+        request_id: Option<String>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"INSERT INTO "audit_log" ("uuid", "old_status", "new_status", "changed_at", "source_ip", "request_id") VALUES (?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(uuid)
+        .bind(old_status)
+        .bind(new_status)
+        .bind(changed_at as i64)
+        .bind(source_ip)
+        .bind(request_id)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn query(
+        conn: &mut SqliteConnection,
+        uuid: Option<&str>,
+        from: Option<u64>,
+        to: Option<u64>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<AuditLogEntry>> {
+        let from = from.map(|from| from as i64);
+        let to = to.map(|to| to as i64);
+        Ok(sqlx::query_as::<_, AuditLogEntry>(
+            r#"SELECT * FROM "audit_log"
+            WHERE (? IS NULL OR "uuid" = ?)
+            AND (? IS NULL OR "changed_at" >= ?)
+            AND (? IS NULL OR "changed_at" <= ?)
+            ORDER BY "id" DESC LIMIT ?"#,
+        )
+        .bind(uuid)
+        .bind(uuid)
+        .bind(from)
+        .bind(from)
+        .bind(to)
+        .bind(to)
+        .bind(limit)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    pub async fn query_changes_since(
+        conn: &mut SqliteConnection,
+        uuid: &str,
+        since: u64,
+    ) -> anyhow::Result<Vec<(i64, String, String)>> {
+        Ok(sqlx::query_as::<_, (i64, String, String)>(
+            r#"SELECT "changed_at", "old_status", "new_status" FROM "audit_log"
+            WHERE "uuid" = ? AND "changed_at" >= ? ORDER BY "changed_at", "id""#,
+        )
+        .bind(uuid)
+        .bind(since as i64)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    /// Status after the last change before `before`, None if no change is logged before it
+    pub async fn query_status_before(
+        conn: &mut SqliteConnection,
+        uuid: &str,
+        before: u64,
+    ) -> anyhow::Result<Option<String>> {
+        Ok(sqlx::query_as::<_, (String,)>(
+            r#"SELECT "new_status" FROM "audit_log"
+            WHERE "uuid" = ? AND "changed_at" < ? ORDER BY "changed_at" DESC, "id" DESC LIMIT 1"#,
+        )
+        .bind(uuid)
+        .bind(before as i64)
+        .fetch_optional(conn)
+        .await?
+        .map(|(status,)| status))
+    }
+
+    pub async fn prune(conn: &mut SqliteConnection, before: u64) -> anyhow::Result<u64> {
+        Ok(
+            sqlx::query(r#"DELETE FROM "audit_log" WHERE "changed_at" < ?"#)
+                .bind(before as i64)
+                .execute(conn)
+                .await?
+                .rows_affected(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct MachineRow {
+    uuid: String,
+    #[serde(serialize_with = "serialize_status_code")]
+    status: i32,
+    last_update: i64,
+    need_push: bool,
+    page: Option<String>,
+    component_id: Option<String>,
+}
+
+impl MachineRow {
+    pub const CSV_HEADER: &'static str = "uuid,status,last_update,need_push,page,component_id";
+
+    pub fn status(&self) -> ServerLastStatus {
+        ServerLastStatus::from_db_code(self.status).unwrap_or(ServerLastStatus::Unknown)
+    }
+    pub fn page(&self) -> &str {
+        self.page.as_deref().unwrap_or_default()
+    }
+    pub fn component_id(&self) -> &str {
+        self.component_id.as_deref().unwrap_or_default()
+    }
+    /// Statuspage component id if mapped, otherwise uuid
+    pub fn upstream_id(&self) -> &str {
+        if self.need_push {
+            self.component_id()
+        } else {
+            &self.uuid
+        }
+    }
+}
+
+fn serialize_status_code<S: Serializer>(code: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(
+        &ServerLastStatus::from_db_code(*code)
+            .unwrap_or(ServerLastStatus::Unknown)
+            .to_string(),
+    )
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl std::fmt::Display for MachineRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{}",
+            escape_csv(&self.uuid),
+            escape_csv(&self.status().to_string()),
+            self.last_update,
+            self.need_push,
+            escape_csv(self.page()),
+            escape_csv(self.component_id())
+        )
+    }
+}
+
+pub async fn get_all_machines(conn: &mut SqliteConnection) -> anyhow::Result<Vec<MachineRow>> {
+    Ok(sqlx::query_as::<_, MachineRow>(
+        r#"SELECT "uuid", "status", "last_update", "need_push", "page", "component_id" FROM "machines""#,
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
+pub async fn get_machine(
+    conn: &mut SqliteConnection,
+    uuid: &str,
+) -> anyhow::Result<Option<MachineRow>> {
+    Ok(sqlx::query_as::<_, MachineRow>(
+        r#"SELECT "uuid", "status", "last_update", "need_push", "page", "component_id" FROM "machines" WHERE "uuid" = ?"#,
+    )
+    .bind(uuid)
+    .fetch_optional(conn)
+    .await?)
+}
+
+pub async fn upsert_component(
+    conn: &mut SqliteConnection,
+    component: &Component,
+) -> anyhow::Result<()> {
+    let page = Some(component.page()).filter(|page| !page.is_empty());
+    let component_id = Some(component.report_id()).filter(|id| !id.is_empty());
+    let tags = serde_json::to_string(component.tags())?;
+    let inserted = sqlx::query(
+        r#"INSERT OR IGNORE INTO "machines" ("uuid", "status", "last_update", "need_push", "page", "component_id", "tags") VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+    )
+    .bind(component.uuid())
+    .bind(ServerLastStatus::Unknown.to_db_code())
+    .bind(get_current_timestamp() as u32)
+    .bind(component.need_push())
+    .bind(page)
+    .bind(component_id)
+    .bind(&tags)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| anyhow!("Insert component {} error: {:?}", component.uuid(), e))?
+    .rows_affected();
+    if inserted > 0 {
+        info!("Insert {} into database", component.uuid());
+        return Ok(());
+    }
+    sqlx::query(
+        r#"UPDATE "machines" SET "need_push" = ?, "page" = ?, "component_id" = ?, "tags" = ? WHERE "uuid" = ?"#,
+    )
+    .bind(component.need_push())
+    .bind(page)
+    .bind(component_id)
+    .bind(&tags)
+    .bind(component.uuid())
+    .execute(conn)
+    .await
+    .map_err(|e| anyhow!("Update component {} error: {:?}", component.uuid(), e))?;
+    Ok(())
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusRecord {
+    uuid: String,
+    status: String,
+    last_update: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportSummary {
+    updated: u64,
+    skipped: u64,
+}
+
+impl ImportSummary {
+    pub fn updated(&self) -> u64 {
+        self.updated
+    }
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+pub async fn import_status<P: AsRef<Path>>(
+    conn: &mut SqliteConnection,
+    path: P,
+) -> anyhow::Result<ImportSummary> {
+    let context = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| anyhow!("Read {} error: {:?}", path.as_ref().display(), e))?;
+    let records: Vec<StatusRecord> = serde_json::from_str(&context)
+        .map_err(|e| anyhow!("Decode {} error: {:?}", path.as_ref().display(), e))?;
+
+    let mut summary = ImportSummary::default();
+    let mut transaction = conn.begin().await?;
+    for record in records {
+        let status = ServerLastStatus::try_from(record.status.as_str()).unwrap_or_else(|_| {
+            warn!(
+                "Unrecognized status {:?} of {}, import as unknown",
+                record.status, record.uuid
+            );
+            ServerLastStatus::Unknown
+        });
+        let affected = sqlx::query(
+            r#"UPDATE "machines" SET "status" = ?, "last_update" = ? WHERE "uuid" = ?"#,
+        )
+        .bind(status.to_db_code())
+        .bind(record.last_update as i64)
+        .bind(&record.uuid)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+        if affected == 0 {
+            warn!("Component {} not found in database, skip", record.uuid);
+            summary.skipped += 1;
+        } else {
+            summary.updated += 1;
+        }
+    }
+    transaction.commit().await?;
+    Ok(summary)
+}
+
+pub async fn reset_component_status(
+    conn: &mut SqliteConnection,
+    uuid: Option<&str>,
+) -> anyhow::Result<()> {
+    let affected = match uuid {
+        Some(uuid) => {
+            let affected = sqlx::query(
+                r#"UPDATE "machines" SET "status" = ?, "last_update" = 0 WHERE "uuid" = ?"#,
+            )
+            .bind(ServerLastStatus::Unknown.to_db_code())
+            .bind(uuid)
+            .execute(conn)
+            .await?
+            .rows_affected();
+            if affected == 0 {
+                return Err(anyhow!("Component {} not found in database", uuid));
+            }
+            affected
+        }
+        None => sqlx::query(r#"UPDATE "machines" SET "status" = ?, "last_update" = 0"#)
+            .bind(ServerLastStatus::Unknown.to_db_code())
+            .execute(conn)
+            .await?
+            .rows_affected(),
+    };
+    info!("Reset status of {} component(s) to unknown", affected);
+    Ok(())
+}
+
+const MACHINES_COLUMNS: [&str; 7] = [
+    "uuid",
+    "status",
+    "last_update",
+    "need_push",
+    "page",
+    "component_id",
+    "tags",
+];
+
+async fn get_columns(conn: &mut SqliteConnection, table: &str) -> anyhow::Result<Vec<String>> {
+    Ok(
+        sqlx::query_as::<_, (String,)>(r#"SELECT "name" FROM pragma_table_info(?)"#)
+            .bind(table)
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .map(|(name,)| name)
+            .collect(),
+    )
+}
+
+pub async fn migrate(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+    // Run each step in its own transaction, so an interrupted migration leaves database at
+    // the previous version instead of a half migrated schema
+    if get_columns(conn, "machines").await?.is_empty() {
+        let mut transaction = conn.begin().await?;
+        sqlx::query(current::CREATE_TABLE)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!("Create database with version {}", current::VERSION);
+        return Ok(());
+    }
+    let version = if get_columns(conn, "upstream_meta").await?.is_empty() {
+        None
+    } else {
+        sqlx::query_as::<_, (String,)>(
+            r#"SELECT "value" FROM "upstream_meta" WHERE "key" = 'version'"#,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|(version,)| version)
+    };
+    let mut version = version.unwrap_or_else(|| v1::VERSION.to_string());
+    if version == v1::VERSION {
+        let mut transaction = conn.begin().await?;
+        if !get_columns(&mut transaction, "machines")
+            .await?
+            .iter()
+            .any(|column| column == "need_push")
+        {
+            sqlx::query(v2::ADD_NEED_PUSH_COLUMN)
+                .execute(&mut transaction)
+                .await?;
+        }
+        sqlx::query(v2::MIGRATE_FROM_V1)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Migrate database from version {} to {}",
+            v1::VERSION,
+            v2::VERSION
+        );
+        version = v2::VERSION.to_string();
+    }
+    if version == v2::VERSION {
+        let mut transaction = conn.begin().await?;
+        sqlx::query(v3::MIGRATE_FROM_V2)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Migrate database from version {} to {}",
+            v2::VERSION,
+            v3::VERSION
+        );
+        version = v3::VERSION.to_string();
+    }
+    if version == v3::VERSION {
+        let mut transaction = conn.begin().await?;
+        sqlx::query(v4::MIGRATE_FROM_V3)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Migrate database from version {} to {}",
+            v3::VERSION,
+            v4::VERSION
+        );
+        version = v4::VERSION.to_string();
+    }
+    if version == v4::VERSION {
+        let mut transaction = conn.begin().await?;
+        sqlx::query(v5::MIGRATE_FROM_V4)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Migrate database from version {} to {}",
+            v4::VERSION,
+            v5::VERSION
+        );
+        version = v5::VERSION.to_string();
+    }
+    if version == v5::VERSION {
+        let mut transaction = conn.begin().await?;
+        sqlx::query(v6::MIGRATE_FROM_V5)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Migrate database from version {} to {}",
+            v5::VERSION,
+            v6::VERSION
+        );
+        version = v6::VERSION.to_string();
+    }
+    if version != current::VERSION {
+        return Err(anyhow!("Unsupported database version: {}", version));
+    }
+    Ok(())
+}
+
+pub async fn validate_schema(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+    let columns = get_columns(conn, "machines").await?;
+    if columns.is_empty() {
+        return Err(anyhow!(
+            "Table \"machines\" not found in database, please create or migrate database first"
+        ));
+    }
+    let missing = MACHINES_COLUMNS
+        .iter()
+        .filter(|column| !columns.iter().any(|name| name == *column))
+        .copied()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Database schema is out of date, missing column(s) {:?} in table \"machines\", please migrate database first",
+            missing
+        ));
+    }
+    for table in ["maintenance_history", "audit_log"] {
+        if get_columns(conn, table).await?.is_empty() {
+            return Err(anyhow!(
+                "Table {:?} not found in database, please migrate database first",
+                table
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub async fn check_json_support(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+    sqlx::query_as::<_, (i64,)>(r#"SELECT COUNT(*) FROM json_each('[]')"#)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| anyhow!("SQLite JSON1 extension is not available: {:?}", e))?;
+    Ok(())
+}
+
 pub fn get_current_timestamp() -> u64 {
     let start = std::time::SystemTime::now();
     let since_the_epoch = start
@@ -23,3 +632,204 @@ pub fn get_current_timestamp() -> u64 {
         .expect("Time went backwards");
     since_the_epoch.as_secs()
 }
+
+pub use v6 as current;
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_columns, get_machine, migrate, reset_component_status, upsert_component,
+        validate_schema,
+    };
+    use crate::configure::Component;
+    use crate::datastructures::ServerLastStatus;
+    use crate::web_service::current::FetchReturnType;
+    use sqlx::sqlite::SqliteConnectOptions;
+    use sqlx::{ConnectOptions, Executor, SqliteConnection};
+    use std::str::FromStr;
+
+    const UUID: &str = "00000000000000000000000000000001";
+
+    async fn memory_database() -> SqliteConnection {
+        let mut conn = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        migrate(&mut conn).await.unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_queries_match_schema() {
+        let mut conn = memory_database().await;
+        validate_schema(&mut conn).await.unwrap();
+        for sql in [
+            r#"SELECT "uuid", "status", "last_update", "need_push", "page", "component_id" FROM "machines" WHERE "uuid" = ?"#,
+            r#"SELECT "uuid", "page", "component_id" FROM "machines" WHERE "uuid" = ?"#,
+            r#"SELECT "status", "tags" FROM "machines" WHERE "uuid" = ? "#,
+            r#"UPDATE "machines" SET "status" = ?, "last_update" = ? WHERE "uuid" = ?"#,
+            r#"SELECT 1 FROM "maintenance_history" WHERE "name" = ?"#,
+            r#"INSERT INTO "maintenance_history" VALUES (?, ?)"#,
+        ] {
+            (&mut conn).prepare(sql).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_component() {
+        let mut conn = memory_database().await;
+        upsert_component(
+            &mut conn,
+            &Component::new(
+                UUID.to_string(),
+                "name".to_string(),
+                "component".to_string(),
+                "page".to_string(),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let machine = get_machine(&mut conn, UUID).await.unwrap().unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Unknown);
+        assert!(get_machine(&mut conn, "not exists")
+            .await
+            .unwrap()
+            .is_none());
+
+        let component = Component::from(
+            sqlx::query_as::<_, FetchReturnType>(
+                r#"SELECT "uuid", "page", "component_id" FROM "machines" WHERE "uuid" = ?"#,
+            )
+            .bind(UUID)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap(),
+        );
+        assert_eq!(component.uuid(), UUID);
+        assert_eq!(component.page(), "page");
+        assert_eq!(component.report_id(), "component");
+        assert!(component.need_push());
+    }
+
+    #[tokio::test]
+    async fn test_reset_component_status() {
+        let mut conn = memory_database().await;
+        for uuid in [UUID, "00000000000000000000000000000002"] {
+            upsert_component(
+                &mut conn,
+                &Component::new(
+                    uuid.to_string(),
+                    "name".to_string(),
+                    "component".to_string(),
+                    "page".to_string(),
+                ),
+            )
+            .await
+            .unwrap();
+        }
+        sqlx::query(r#"UPDATE "machines" SET "status" = ?"#)
+            .bind(ServerLastStatus::Optional.to_db_code())
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        assert!(reset_component_status(&mut conn, Some("not exists"))
+            .await
+            .is_err());
+        for uuid in [UUID, "00000000000000000000000000000002"] {
+            let machine = get_machine(&mut conn, uuid).await.unwrap().unwrap();
+            assert_eq!(machine.status(), ServerLastStatus::Optional);
+        }
+
+        reset_component_status(&mut conn, Some(UUID)).await.unwrap();
+        let machine = get_machine(&mut conn, UUID).await.unwrap().unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Unknown);
+        let machine = get_machine(&mut conn, "00000000000000000000000000000002")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Optional);
+
+        reset_component_status(&mut conn, None).await.unwrap();
+        let machine = get_machine(&mut conn, "00000000000000000000000000000002")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_from_v3() {
+        let mut conn = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"CREATE TABLE "machines" (
+                "uuid"	TEXT NOT NULL,
+                "status"	TEXT NOT NULL DEFAULT 'unknown',
+                "last_update"	INTEGER NOT NULL,
+                "need_push"	INTEGER NOT NULL DEFAULT 0,
+                "page"	TEXT,
+                "component_id"	TEXT,
+                "tags"	TEXT NOT NULL DEFAULT '[]',
+                PRIMARY KEY("uuid")
+            );
+            CREATE TABLE "upstream_meta" (
+                "key"	TEXT NOT NULL,
+                "value"	TEXT NOT NULL,
+                PRIMARY KEY("key")
+            );
+            INSERT INTO "upstream_meta" VALUES ('version', '3');
+            INSERT INTO "machines" VALUES ('00000000000000000000000000000001', 'operational', 0, 1, 'page', 'component', '[]');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        migrate(&mut conn).await.unwrap();
+        validate_schema(&mut conn).await.unwrap();
+        let machine = get_machine(&mut conn, UUID).await.unwrap().unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Optional);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_failure_rollback() {
+        let mut conn = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        // Version row can not be written, so v1 to v2 step fails after adding "need_push"
+        sqlx::query(
+            r#"CREATE TABLE "machines" (
+                "uuid"	TEXT NOT NULL,
+                "status"	TEXT NOT NULL,
+                "last_update"	INTEGER NOT NULL,
+                "page"	TEXT,
+                "component_id"	TEXT,
+                PRIMARY KEY("uuid")
+            );
+            CREATE TABLE "upstream_meta" (
+                "key"	TEXT NOT NULL,
+                "value"	TEXT NOT NULL CHECK ("value" != '2'),
+                PRIMARY KEY("key")
+            );
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        assert!(migrate(&mut conn).await.is_err());
+        assert!(!get_columns(&mut conn, "machines")
+            .await
+            .unwrap()
+            .iter()
+            .any(|column| column == "need_push"));
+    }
+}
@@ -1,29 +1,142 @@
 use crate::statuspagelib::ComponentStatus;
+use anyhow::anyhow;
 use async_trait::async_trait;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::Formatter;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct TransferData {
     status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
 }
 
 impl TransferData {
     pub fn new(status: String) -> Self {
-        Self { status }
+        Self { status, tags: None }
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = Some(tags);
+        self
     }
 
     pub fn not_found() -> Self {
+        Self::new("NOT_FOUND".to_string())
+    }
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ComponentItem {
+    uuid: String,
+    name: String,
+    status: String,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    match_reason: Option<String>,
+}
+
+impl ComponentItem {
+    pub fn new(
+        uuid: String,
+        name: String,
+        status: String,
+        tags: Vec<String>,
+        match_reason: Option<String>,
+    ) -> Self {
         Self {
-            status: "NOT_FOUND".to_string(),
+            uuid,
+            name,
+            status,
+            tags,
+            match_reason,
         }
     }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PageSummary {
+    page_id: String,
+    status: String,
+    component_count: u64,
+    operational_count: u64,
+}
+
+impl PageSummary {
+    pub fn new(page_id: String) -> Self {
+        Self {
+            page_id,
+            status: ServerLastStatus::Optional.to_string(),
+            component_count: 0,
+            operational_count: 0,
+        }
+    }
+
+    pub fn add(&mut self, status: ServerLastStatus, count: u64) {
+        self.component_count += count;
+        if status == ServerLastStatus::Optional {
+            self.operational_count += count;
+        }
+        let current =
+            ServerLastStatus::try_from(self.status.as_str()).unwrap_or(ServerLastStatus::Unknown);
+        if status.severity() > current.severity() {
+            self.status = status.to_string();
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TimelineInterval {
+    status: String,
+    start_time: u64,
+    end_time: u64,
+}
+
+impl TimelineInterval {
+    pub fn new(status: ServerLastStatus, start_time: u64, end_time: u64) -> Self {
+        Self {
+            status: status.to_string(),
+            start_time,
+            end_time,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct StatusEvent {
+    uuid: String,
+    status: String,
+    last_update: u64,
+}
+
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+impl StatusEvent {
+    pub fn new(uuid: String, status: String, last_update: u64) -> Self {
+        Self {
+            uuid,
+            status,
+            last_update,
+        }
+    }
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
     pub fn status(&self) -> &str {
         &self.status
     }
+    pub fn last_update(&self) -> u64 {
+        self.last_update
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ServerLastStatus {
     Optional,
     Outage,
@@ -49,11 +162,54 @@ impl TryFrom<&str> for ServerLastStatus {
             "major_outage" => Self::Outage,
             "partial_outage" => Self::PartialOutage,
             "degraded_performance" => ServerLastStatus::DegradedPerformance,
-            _ => Self::Unknown,
+            "unknown" => Self::Unknown,
+            "ok" | "up" => Self::Optional,
+            "down" => Self::Outage,
+            _ => return Err(anyhow!("unrecognized status: {}", value)),
         })
     }
 }
 
+impl ServerLastStatus {
+    pub fn severity(&self) -> u8 {
+        match self {
+            ServerLastStatus::Optional => 0,
+            ServerLastStatus::Unknown => 1,
+            ServerLastStatus::DegradedPerformance => 2,
+            ServerLastStatus::PartialOutage => 3,
+            ServerLastStatus::Outage => 4,
+        }
+    }
+
+    pub fn to_db_code(self) -> i32 {
+        match self {
+            ServerLastStatus::Optional => 0,
+            ServerLastStatus::DegradedPerformance => 1,
+            ServerLastStatus::PartialOutage => 2,
+            ServerLastStatus::Outage => 3,
+            ServerLastStatus::Unknown => 4,
+        }
+    }
+
+    pub fn from_db_code(code: i32) -> anyhow::Result<Self> {
+        Ok(match code {
+            0 => ServerLastStatus::Optional,
+            1 => ServerLastStatus::DegradedPerformance,
+            2 => ServerLastStatus::PartialOutage,
+            3 => ServerLastStatus::Outage,
+            4 => ServerLastStatus::Unknown,
+            _ => return Err(anyhow!("unrecognized status code: {}", code)),
+        })
+    }
+
+    pub fn parse_with_aliases(
+        value: &str,
+        aliases: &HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        Self::try_from(aliases.get(value).map(|s| s.as_str()).unwrap_or(value))
+    }
+}
+
 impl std::fmt::Display for ServerLastStatus {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -75,12 +231,41 @@ pub trait UpstreamTrait: Send + Sync {
     #[deprecated]
     async fn get_component_status(&self, component: &str, page: &str) -> anyhow::Result<()>;
 
+    async fn get_component_name(&self, _component: &str, _page: &str) -> anyhow::Result<String> {
+        Err(anyhow!("Upstream not support fetch component name"))
+    }
+
+    async fn get_component_names(&self, _page: &str) -> anyhow::Result<HashMap<String, String>> {
+        Err(anyhow!("Upstream not support fetch all component names"))
+    }
+
     async fn set_component_status(
         &self,
         component: &str,
         page: &str,
         status: ComponentStatus,
     ) -> anyhow::Result<()>;
+
+    // Called when component reports unchanged operational status, which skips `set_component_status`
+    async fn heartbeat(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // Extension point for upstream specific fields, no caller sends raw payload yet
+    #[allow(dead_code)]
+    async fn set_raw_status(
+        &self,
+        component: &str,
+        page: &str,
+        payload: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let status = payload
+            .get("status")
+            .and_then(|status| status.as_str())
+            .ok_or_else(|| anyhow!("Field \"status\" not found in payload"))?;
+        self.set_component_status(component, page, ComponentStatus::try_from(status)?)
+            .await
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -101,3 +286,303 @@ impl UpstreamTrait for EmptyUpstream {
         Ok(())
     }
 }
+
+/// Upstream which can be replaced while running, e.g. rebuilt after configure reload
+pub struct ReloadableUpstream(RwLock<Arc<dyn UpstreamTrait>>);
+
+impl ReloadableUpstream {
+    pub fn new(upstream: Arc<dyn UpstreamTrait>) -> Self {
+        Self(RwLock::new(upstream))
+    }
+
+    pub async fn replace(&self, upstream: Arc<dyn UpstreamTrait>) {
+        *self.0.write().await = upstream;
+    }
+
+    // Do not hold lock while waiting upstream, so replace is not blocked by slow request
+    async fn current(&self) -> Arc<dyn UpstreamTrait> {
+        self.0.read().await.clone()
+    }
+}
+
+#[async_trait]
+#[allow(deprecated)]
+impl UpstreamTrait for ReloadableUpstream {
+    async fn get_component_status(&self, component: &str, page: &str) -> anyhow::Result<()> {
+        self.current()
+            .await
+            .get_component_status(component, page)
+            .await
+    }
+
+    async fn get_component_name(&self, component: &str, page: &str) -> anyhow::Result<String> {
+        self.current()
+            .await
+            .get_component_name(component, page)
+            .await
+    }
+
+    async fn get_component_names(&self, page: &str) -> anyhow::Result<HashMap<String, String>> {
+        self.current().await.get_component_names(page).await
+    }
+
+    async fn set_component_status(
+        &self,
+        component: &str,
+        page: &str,
+        status: ComponentStatus,
+    ) -> anyhow::Result<()> {
+        self.current()
+            .await
+            .set_component_status(component, page, status)
+            .await
+    }
+
+    async fn heartbeat(&self, component: &str, page: &str) -> anyhow::Result<()> {
+        self.current().await.heartbeat(component, page).await
+    }
+
+    async fn set_raw_status(
+        &self,
+        component: &str,
+        page: &str,
+        payload: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        self.current()
+            .await
+            .set_raw_status(component, page, payload)
+            .await
+    }
+}
+
+pub struct MultipleUpstream(Vec<Box<dyn UpstreamTrait>>);
+
+impl MultipleUpstream {
+    pub fn new(upstreams: Vec<Box<dyn UpstreamTrait>>) -> Self {
+        Self(upstreams)
+    }
+}
+
+#[async_trait]
+#[allow(deprecated)]
+impl UpstreamTrait for MultipleUpstream {
+    async fn get_component_status(&self, component: &str, page: &str) -> anyhow::Result<()> {
+        for upstream in &self.0 {
+            upstream.get_component_status(component, page).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_component_name(&self, component: &str, page: &str) -> anyhow::Result<String> {
+        let mut last_error = anyhow!("No upstream available");
+        for upstream in &self.0 {
+            match upstream.get_component_name(component, page).await {
+                Ok(name) => return Ok(name),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn get_component_names(&self, page: &str) -> anyhow::Result<HashMap<String, String>> {
+        let mut last_error = anyhow!("No upstream available");
+        for upstream in &self.0 {
+            match upstream.get_component_names(page).await {
+                Ok(names) => return Ok(names),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn set_component_status(
+        &self,
+        component: &str,
+        page: &str,
+        status: ComponentStatus,
+    ) -> anyhow::Result<()> {
+        let mut ret = Ok(());
+        for upstream in &self.0 {
+            if let Err(e) = upstream.set_component_status(component, page, status).await {
+                ret = Err(e);
+            }
+        }
+        ret
+    }
+
+    async fn heartbeat(&self, component: &str, page: &str) -> anyhow::Result<()> {
+        let mut ret = Ok(());
+        for upstream in &self.0 {
+            if let Err(e) = upstream.heartbeat(component, page).await {
+                ret = Err(e);
+            }
+        }
+        ret
+    }
+
+    async fn set_raw_status(
+        &self,
+        component: &str,
+        page: &str,
+        payload: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let mut ret = Ok(());
+        for upstream in &self.0 {
+            if let Err(e) = upstream
+                .set_raw_status(component, page, payload.clone())
+                .await
+            {
+                ret = Err(e);
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComponentItem, ServerLastStatus, TransferData};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const ALL_STATUS: [ServerLastStatus; 5] = [
+        ServerLastStatus::Optional,
+        ServerLastStatus::DegradedPerformance,
+        ServerLastStatus::PartialOutage,
+        ServerLastStatus::Outage,
+        ServerLastStatus::Unknown,
+    ];
+
+    #[test]
+    fn test_transfer_data_round_trip() {
+        for status in ALL_STATUS {
+            for data in [
+                TransferData::new(status.to_string()),
+                TransferData::new(status.to_string()).with_tags(vec!["production".to_string()]),
+            ] {
+                let decoded: TransferData =
+                    serde_json::from_str(&serde_json::to_string(&data).unwrap()).unwrap();
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_data_empty_status() {
+        let data: TransferData = serde_json::from_str(r#"{"status": ""}"#).unwrap();
+        assert_eq!(data.status(), "");
+        assert!(ServerLastStatus::try_from(data.status()).is_err());
+    }
+
+    #[test]
+    fn test_transfer_data_skip_empty_tags() {
+        assert_eq!(
+            serde_json::to_value(TransferData::new("operational".to_string())).unwrap(),
+            json!({"status": "operational"})
+        );
+    }
+
+    #[test]
+    fn test_transfer_data_malformed() {
+        for input in [
+            "",
+            "{",
+            "{}",
+            "[]",
+            r#"{"status": 1}"#,
+            r#"{"status": null}"#,
+            r#"{"status": "operational", "tags": "production"}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<TransferData>(input).is_err(),
+                "{:?} should not be decoded",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_server_last_status_round_trip() {
+        for status in ALL_STATUS {
+            assert_eq!(
+                ServerLastStatus::try_from(status.to_string().as_str()).unwrap(),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn test_server_last_status_aliases() {
+        assert_eq!(
+            ServerLastStatus::try_from("ok").unwrap(),
+            ServerLastStatus::Optional
+        );
+        assert_eq!(
+            ServerLastStatus::try_from("up").unwrap(),
+            ServerLastStatus::Optional
+        );
+        assert_eq!(
+            ServerLastStatus::try_from("down").unwrap(),
+            ServerLastStatus::Outage
+        );
+        assert!(ServerLastStatus::try_from("Operational").is_err());
+
+        let aliases = HashMap::from([("healthy".to_string(), "operational".to_string())]);
+        assert_eq!(
+            ServerLastStatus::parse_with_aliases("healthy", &aliases).unwrap(),
+            ServerLastStatus::Optional
+        );
+        assert_eq!(
+            ServerLastStatus::parse_with_aliases("major_outage", &aliases).unwrap(),
+            ServerLastStatus::Outage
+        );
+        assert!(ServerLastStatus::parse_with_aliases("sick", &aliases).is_err());
+    }
+
+    #[test]
+    fn test_server_last_status_db_code() {
+        for (code, status) in [
+            (0, ServerLastStatus::Optional),
+            (1, ServerLastStatus::DegradedPerformance),
+            (2, ServerLastStatus::PartialOutage),
+            (3, ServerLastStatus::Outage),
+            (4, ServerLastStatus::Unknown),
+        ] {
+            assert_eq!(status.to_db_code(), code);
+            assert_eq!(ServerLastStatus::from_db_code(code).unwrap(), status);
+        }
+        assert!(ServerLastStatus::from_db_code(-1).is_err());
+        assert!(ServerLastStatus::from_db_code(5).is_err());
+    }
+
+    #[test]
+    fn test_component_item_serialize() {
+        let item = ComponentItem::new(
+            "uuid".to_string(),
+            "name".to_string(),
+            ServerLastStatus::Optional.to_string(),
+            vec!["production".to_string()],
+            None,
+        );
+        assert_eq!(
+            serde_json::to_value(item).unwrap(),
+            json!({
+                "uuid": "uuid",
+                "name": "name",
+                "status": "operational",
+                "tags": ["production"],
+            })
+        );
+        let item = ComponentItem::new(
+            "uuid".to_string(),
+            "name".to_string(),
+            ServerLastStatus::Outage.to_string(),
+            vec![],
+            Some("name".to_string()),
+        );
+        assert_eq!(
+            serde_json::to_value(item).unwrap()["match_reason"],
+            json!("name")
+        );
+    }
+}
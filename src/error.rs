@@ -0,0 +1,128 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::datastructures::TransferData;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+#[cfg(any(feature = "env_logger", feature = "log4rs"))]
+use log::{debug, error};
+use serde_json::json;
+#[cfg(feature = "spdlog-rs")]
+use spdlog::prelude::*;
+
+#[derive(Debug)]
+pub enum StatusUpstreamError {
+    BadRequest(String),
+    NotFound,
+    Database(sqlx::Error),
+    Upstream(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl std::fmt::Display for StatusUpstreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusUpstreamError::BadRequest(reason) => write!(f, "Bad request: {}", reason),
+            StatusUpstreamError::NotFound => write!(f, "Not found"),
+            StatusUpstreamError::Database(e) => write!(f, "Database error: {:?}", e),
+            StatusUpstreamError::Upstream(e) => write!(f, "Upstream error: {:?}", e),
+            StatusUpstreamError::Internal(e) => write!(f, "Internal error: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for StatusUpstreamError {}
+
+impl From<sqlx::Error> for StatusUpstreamError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            e => Self::Database(e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for StatusUpstreamError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<sqlx::Error>() {
+            Ok(e) => Self::from(e),
+            Err(e) => Self::Internal(e),
+        }
+    }
+}
+
+impl IntoResponse for StatusUpstreamError {
+    fn into_response(self) -> Response {
+        match self {
+            // Caused by client, not worth an error in server log
+            StatusUpstreamError::BadRequest(reason) => {
+                debug!("Bad request: {}", reason);
+                (
+                    StatusCode::BAD_REQUEST,
+                    json!({ "status": 400, "reason": reason }).to_string(),
+                )
+                    .into_response()
+            }
+            StatusUpstreamError::NotFound => {
+                debug!("{}", self);
+                (StatusCode::NOT_FOUND, Json(TransferData::not_found())).into_response()
+            }
+            StatusUpstreamError::Database(_)
+            | StatusUpstreamError::Upstream(_)
+            | StatusUpstreamError::Internal(_) => {
+                error!("{}", self);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({ "status": 500 }).to_string(),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusUpstreamError;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn test_bad_request_body() {
+        let response = StatusUpstreamError::BadRequest("unexpected status: broken".to_string())
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({"status": 400, "reason": "unexpected status: broken"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_body() {
+        let response =
+            StatusUpstreamError::Internal(anyhow::anyhow!("secret detail")).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({"status": 500})
+        );
+    }
+}
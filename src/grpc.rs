@@ -0,0 +1,237 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+pub mod proto {
+    tonic::include_proto!("status_upstream.v1");
+}
+
+pub mod v1 {
+    use super::proto::status_service_server::{StatusService, StatusServiceServer};
+    use super::proto::{
+        ComponentStatus, GetStatusRequest, ListComponentsRequest, ListComponentsResponse,
+        SetStatusRequest, SubscribeRequest,
+    };
+    use crate::database::{audit, get_current_timestamp, get_machine};
+    use crate::datastructures::{ServerLastStatus, StatusEvent, UpstreamTrait};
+    #[cfg(any(feature = "env_logger", feature = "log4rs"))]
+    use log::error;
+    #[cfg(feature = "spdlog-rs")]
+    use spdlog::prelude::*;
+    use sqlx::SqlitePool;
+    use std::collections::HashMap;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
+    use tokio_stream::wrappers::BroadcastStream;
+    use tokio_stream::{Stream, StreamExt};
+    use tonic::{Request, Response, Status};
+
+    impl From<StatusEvent> for ComponentStatus {
+        fn from(event: StatusEvent) -> Self {
+            Self {
+                uuid: event.uuid().to_string(),
+                status: event.status().to_string(),
+                last_update: event.last_update(),
+            }
+        }
+    }
+
+    pub struct StatusServiceImpl {
+        pool: SqlitePool,
+        upstream: Arc<dyn UpstreamTrait>,
+        events: broadcast::Sender<StatusEvent>,
+        status_aliases: HashMap<String, String>,
+    }
+
+    impl StatusServiceImpl {
+        pub fn new(
+            pool: SqlitePool,
+            upstream: Arc<dyn UpstreamTrait>,
+            events: broadcast::Sender<StatusEvent>,
+            status_aliases: HashMap<String, String>,
+        ) -> Self {
+            Self {
+                pool,
+                upstream,
+                events,
+                status_aliases,
+            }
+        }
+
+        pub fn into_server(self) -> StatusServiceServer<Self> {
+            StatusServiceServer::new(self)
+        }
+    }
+
+    #[tonic::async_trait]
+    impl StatusService for StatusServiceImpl {
+        async fn get_status(
+            &self,
+            request: Request<GetStatusRequest>,
+        ) -> Result<Response<ComponentStatus>, Status> {
+            let uuid = request.into_inner().uuid;
+            let ret = sqlx::query_as::<_, (i32, i64)>(
+                r#"SELECT "status", "last_update" FROM "machines" WHERE "uuid" = ?"#,
+            )
+            .bind(&uuid)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Got error while fetching component {} status: {:?}",
+                    &uuid, e
+                );
+                Status::internal("database error")
+            })?;
+            match ret {
+                None => Err(Status::not_found(format!("component {} not found", uuid))),
+                Some((status, last_update)) => Ok(Response::new(ComponentStatus {
+                    uuid,
+                    status: ServerLastStatus::from_db_code(status)
+                        .unwrap_or(ServerLastStatus::Unknown)
+                        .to_string(),
+                    last_update: last_update as u64,
+                })),
+            }
+        }
+
+        async fn set_status(
+            &self,
+            request: Request<SetStatusRequest>,
+        ) -> Result<Response<ComponentStatus>, Status> {
+            let remote_addr = request.remote_addr();
+            let SetStatusRequest { uuid, status } = request.into_inner();
+            let last_status = ServerLastStatus::parse_with_aliases(&status, &self.status_aliases)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            if last_status == ServerLastStatus::Unknown {
+                return Err(Status::invalid_argument(format!(
+                    "unexpected status: {}",
+                    status
+                )));
+            }
+            let status = last_status.to_string();
+
+            let mut conn = self.pool.acquire().await.map_err(|e| {
+                error!("Acquire database connection error: {:?}", e);
+                Status::internal("database error")
+            })?;
+            let machine = get_machine(&mut conn, &uuid)
+                .await
+                .map_err(|e| {
+                    error!("Fetch {} component error: {:?}", &uuid, e);
+                    Status::internal("database error")
+                })?
+                .ok_or_else(|| Status::not_found(format!("component {} not found", uuid)))?;
+
+            // Push before saving, so that a retry after upstream error will push again
+            if machine.status() != last_status {
+                self.upstream
+                    .set_component_status(machine.upstream_id(), machine.page(), last_status.into())
+                    .await
+                    .map_err(|e| {
+                        error!("Got error while upload status to server: {:?}", e);
+                        Status::unavailable("upstream error")
+                    })?;
+            } else if last_status == ServerLastStatus::Optional {
+                self.upstream
+                    .heartbeat(machine.upstream_id(), machine.page())
+                    .await
+                    .map_err(|e| {
+                        error!("Got error while sending heartbeat to server: {:?}", e);
+                        Status::unavailable("upstream error")
+                    })?;
+            }
+
+            let current_time = get_current_timestamp();
+            sqlx::query(
+                r#"UPDATE "machines" SET "status" = ?, "last_update" = ? WHERE "uuid" = ?"#,
+            )
+            .bind(last_status.to_db_code())
+            .bind(current_time as u32)
+            .bind(&uuid)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Update database for {} to {} error: {:?}",
+                    &uuid, &status, e
+                );
+                Status::internal("database error")
+            })?;
+            if machine.status() != last_status {
+                audit::insert(
+                    &mut conn,
+                    &uuid,
+                    &machine.status().to_string(),
+                    &status,
+                    current_time,
+                    remote_addr.map(|addr| addr.ip().to_string()),
+                    None,
+                )
+                .await
+                .map_err(|e| error!("Insert audit log for {} error: {:?}", &uuid, e))
+                .ok();
+            }
+            drop(conn);
+
+            let event = StatusEvent::new(uuid, status, current_time);
+            self.events.send(event.clone()).ok();
+
+            Ok(Response::new(event.into()))
+        }
+
+        async fn list_components(
+            &self,
+            _request: Request<ListComponentsRequest>,
+        ) -> Result<Response<ListComponentsResponse>, Status> {
+            let components = sqlx::query_as::<_, (String, i32, i64)>(
+                r#"SELECT "uuid", "status", "last_update" FROM "machines""#,
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Got error while fetching all components: {:?}", e);
+                Status::internal("database error")
+            })?
+            .into_iter()
+            .map(|(uuid, status, last_update)| ComponentStatus {
+                uuid,
+                status: ServerLastStatus::from_db_code(status)
+                    .unwrap_or(ServerLastStatus::Unknown)
+                    .to_string(),
+                last_update: last_update as u64,
+            })
+            .collect();
+            Ok(Response::new(ListComponentsResponse { components }))
+        }
+
+        type SubscribeStream =
+            Pin<Box<dyn Stream<Item = Result<ComponentStatus, Status>> + Send + 'static>>;
+
+        async fn subscribe(
+            &self,
+            _request: Request<SubscribeRequest>,
+        ) -> Result<Response<Self::SubscribeStream>, Status> {
+            // Lagged receiver only lose some events, skip them
+            let stream = BroadcastStream::new(self.events.subscribe())
+                .filter_map(|event| event.ok().map(|event| Ok(event.into())));
+            Ok(Response::new(Box::pin(stream)))
+        }
+    }
+}
+
+pub use v1::StatusServiceImpl;
@@ -20,115 +20,510 @@ compile_error!("You should choose only one log feature");
 
 use crate::configure::Configure;
 use crate::database::get_current_timestamp;
-use crate::datastructures::{EmptyUpstream, UpstreamTrait};
+use crate::datastructures::{
+    EmptyUpstream, MultipleUpstream, ReloadableUpstream, ServerLastStatus, UpstreamTrait,
+};
 use crate::statuspagelib::StatusPageUpstream;
-use crate::web_service::v1::make_router;
+use crate::upstreams::betterstack::BetterStackUpstream;
+use crate::upstreams::discord::DiscordUpstream;
+#[cfg(feature = "freshping")]
+use crate::upstreams::freshping::FreshpingUpstream;
+use crate::upstreams::matrix::MatrixUpstream;
+#[cfg(feature = "pagerduty")]
+use crate::upstreams::pagerduty::PagerDutyUpstream;
+use crate::web_service::v1::{make_router, AppState};
 use anyhow::anyhow;
+use chrono::TimeZone;
 use clap::{arg, Command};
 #[cfg(any(feature = "env_logger", feature = "log4rs"))]
-use log::{info, warn};
+use log::{debug, error, info, warn};
 #[cfg(feature = "spdlog-rs")]
-use spdlog::{default_logger, init_log_crate_proxy, prelude::*, sink::FileSink};
-use sqlx::sqlite::SqliteConnectOptions;
-use sqlx::{ConnectOptions, SqliteConnection};
+use spdlog::{
+    default_logger, init_log_crate_proxy,
+    prelude::*,
+    sink::{RotatingFileSink, RotationPolicy},
+};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{ConnectOptions, Connection, SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
 
 mod configure;
 mod database;
 mod datastructures;
+mod error;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod maintenance;
 mod statuspagelib;
+mod upstreams;
 mod web_service;
 
 const DEFAULT_DATABASE_LOCATION: &str = "database.db";
+const AUDIT_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const DATABASE_CONNECT_ATTEMPTS: u32 = 5;
+const DATABASE_CONNECT_BACKOFF: Duration = Duration::from_millis(500);
 
-async fn check_database(
-    config: &Configure,
-    mut conn: SqliteConnection,
-) -> anyhow::Result<SqliteConnection> {
+async fn resolve_component_names(config: &mut Configure, upstream: &dyn UpstreamTrait) {
+    let mut pages = HashMap::new();
     for component in config.components() {
-        let ret = sqlx::query_as::<_, (i32,)>(r#"SELECT 1 FROM "machines" WHERE "uuid" = ?"#)
-            .bind(component.uuid())
-            .fetch_optional(&mut conn)
-            .await
-            .map_err(|e| {
-                anyhow!(
-                    "Get component error in check_database function {}: {:?}",
-                    component.uuid(),
-                    e
-                )
-            })?;
-        if ret.is_none() {
-            sqlx::query(r#"INSERT INTO "machines" VALUES (?, 'unknown', ?, ?, ?, ?)"#)
-                .bind(component.uuid())
-                .bind(get_current_timestamp() as u32)
-                .bind(component.need_push())
-                .bind(if component.page().is_empty() {
-                    None
-                } else {
-                    Some(component.page().to_string())
-                })
-                .bind(if component.report_id().is_empty() {
-                    None
-                } else {
-                    Some(component.report_id().to_string())
-                })
-                .execute(&mut conn)
+        if component.need_push() && !pages.contains_key(component.page()) {
+            let names = upstream
+                .get_component_names(component.page())
                 .await
                 .map_err(|e| {
-                    anyhow!(
-                        "Insert component error in check_database function {}: {:?}",
-                        component.uuid(),
+                    debug!(
+                        "Fetch components of page {} error, fallback to fetch one by one: {:?}",
+                        component.page(),
                         e
                     )
-                })?;
-            info!("Insert {} into database", component.uuid())
+                })
+                .ok();
+            pages.insert(component.page().to_string(), names);
+        }
+    }
+
+    for component in config.components_mut() {
+        if !component.need_push() {
+            continue;
+        }
+        let name = match pages.get(component.page()) {
+            Some(Some(names)) => names
+                .get(component.report_id())
+                .cloned()
+                .ok_or_else(|| anyhow!("Component not found in page {}", component.page())),
+            _ => {
+                upstream
+                    .get_component_name(component.report_id(), component.page())
+                    .await
+            }
+        };
+        match name {
+            Ok(name) => {
+                debug!("Resolve component {} name to {:?}", component.uuid(), name);
+                component.set_name(name)
+            }
+            Err(e) => warn!(
+                "Fetch component {} name error, fallback to {:?}: {:?}",
+                component.uuid(),
+                component.name(),
+                e
+            ),
         }
-        // Current not check uuid not in database.
     }
+}
+
+fn is_database_locked(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => {
+            // SQLITE_BUSY and SQLITE_LOCKED
+            matches!(e.code().as_deref(), Some("5") | Some("6"))
+                || e.message().contains("database is locked")
+        }
+        _ => false,
+    }
+}
+
+async fn connect_database(location: &str) -> anyhow::Result<SqliteConnection> {
+    let mut attempt = 1;
+    loop {
+        match SqliteConnectOptions::new()
+            .filename(location)
+            .create_if_missing(true)
+            .connect()
+            .await
+        {
+            Ok(conn) => return Ok(conn),
+            Err(e) if is_database_locked(&e) && attempt < DATABASE_CONNECT_ATTEMPTS => {
+                warn!(
+                    "Database {} is locked, retry in {:?} ({}/{})",
+                    location, DATABASE_CONNECT_BACKOFF, attempt, DATABASE_CONNECT_ATTEMPTS
+                );
+                attempt += 1;
+                tokio::time::sleep(DATABASE_CONNECT_BACKOFF).await;
+            }
+            Err(e) => return Err(anyhow!("Open database {} error: {:?}", location, e)),
+        }
+    }
+}
+
+async fn open_database(location: &str) -> anyhow::Result<SqliteConnection> {
+    let mut conn = connect_database(location).await?;
+    database::migrate(&mut conn)
+        .await
+        .map_err(|e| anyhow!("Migrate database error: {:?}", e))?;
+    database::validate_schema(&mut conn).await?;
     Ok(conn)
 }
 
-async fn async_main(config_file: &str) -> anyhow::Result<()> {
-    let config = Configure::init_from_path(config_file)
+// Database should be migrated by `open_database` before
+async fn open_pool(location: &str) -> anyhow::Result<SqlitePool> {
+    SqlitePoolOptions::new()
+        .connect_with(SqliteConnectOptions::new().filename(location))
         .await
-        .map_err(|e| anyhow!("Read configure file failure: {:?}", e))?;
+        .map_err(|e| anyhow!("Open database pool {} error: {:?}", location, e))
+}
 
-    let upstream: Box<dyn UpstreamTrait> = if config.statuspage().enabled() {
-        Box::new(StatusPageUpstream::from_configure(&config)?.unwrap())
-    } else {
-        Box::new(EmptyUpstream::default())
-    };
+#[tracing::instrument(skip_all, fields(components = config.components().len()))]
+async fn check_database(config: &Configure, conn: &mut SqliteConnection) -> anyhow::Result<()> {
+    for component in config.components() {
+        database::upsert_component(conn, component).await?;
+        // Current not check uuid not in database.
+    }
+    Ok(())
+}
 
-    let sqlite_connection = SqliteConnectOptions::new()
-        .filename(config.server().database_location())
-        .connect()
-        .await
-        .map_err(|e| {
-            anyhow!(
-                "Open database {} error: {:?}",
-                config.server().database_location(),
-                e
+fn build_upstream(config: &Configure) -> anyhow::Result<Box<dyn UpstreamTrait>> {
+    let mut upstreams: Vec<Box<dyn UpstreamTrait>> = Vec::new();
+    if config.statuspage().enabled() {
+        match StatusPageUpstream::from_configure(config)
+            .map_err(|e| anyhow!("Build statuspage upstream error: {:?}", e))?
+        {
+            Some(upstream) => upstreams.push(Box::new(upstream)),
+            None => return Err(anyhow!("Statuspage is enabled but upstream is not built")),
+        }
+    }
+    #[cfg(feature = "pagerduty")]
+    if let Some(upstream) = PagerDutyUpstream::from_configure(config)? {
+        upstreams.push(Box::new(upstream));
+    }
+    #[cfg(feature = "freshping")]
+    if let Some(upstream) = FreshpingUpstream::from_configure(config)? {
+        upstreams.push(Box::new(upstream));
+    }
+    if let Some(upstream) = DiscordUpstream::from_configure(config)? {
+        upstreams.push(Box::new(upstream));
+    }
+    if let Some(upstream) = MatrixUpstream::from_configure(config)? {
+        upstreams.push(Box::new(upstream));
+    }
+    if let Some(upstream) = BetterStackUpstream::from_configure(config)? {
+        upstreams.push(Box::new(upstream));
+    }
+    Ok(match upstreams.len() {
+        0 => Box::new(EmptyUpstream::default()),
+        1 => upstreams.pop().unwrap(),
+        _ => Box::new(MultipleUpstream::new(upstreams)),
+    })
+}
+
+async fn list_components(config: &Configure) -> anyhow::Result<()> {
+    let mut conn = open_database(&config.server().database_location()).await?;
+    let rows = sqlx::query_as::<_, (String, i32, i64, bool, Option<String>)>(
+        r#"SELECT "uuid", "status", "last_update", "need_push", "page" FROM "machines""#,
+    )
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| anyhow!("Query components error: {:?}", e))?;
+
+    println!("UUID\tName\tStatus\tLast Update\tPage\tNeed Push");
+    for (uuid, status, last_update, need_push, page) in rows {
+        let name = config
+            .components()
+            .iter()
+            .find(|component| component.uuid() == uuid)
+            .map(|component| component.name().to_string())
+            .unwrap_or_default();
+        let status = ServerLastStatus::from_db_code(status).unwrap_or(ServerLastStatus::Unknown);
+        let last_update = chrono::Utc
+            .timestamp_opt(last_update, 0)
+            .single()
+            .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+            .unwrap_or_default();
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            uuid,
+            name,
+            status,
+            last_update,
+            page.unwrap_or_default(),
+            need_push
+        );
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn wait_terminate_signal() {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .unwrap()
+        .recv()
+        .await;
+}
+
+#[cfg(not(unix))]
+async fn wait_terminate_signal() {
+    std::future::pending::<()>().await
+}
+
+#[cfg(unix)]
+async fn apply_config_diff(
+    diff: &configure::ConfigDiff,
+    pool: &SqlitePool,
+    upstream: &dyn UpstreamTrait,
+) {
+    for component in diff.removed_components() {
+        info!("Component {} removed from configure", component.uuid());
+        if !component.need_push() {
+            continue;
+        }
+        upstream
+            .set_component_status(
+                component.report_id(),
+                component.page(),
+                statuspagelib::ComponentStatus::Operational,
             )
-        })?;
+            .await
+            .map_err(|e| {
+                warn!(
+                    "Reset removed component {} status error: {:?}",
+                    component.uuid(),
+                    e
+                )
+            })
+            .ok();
+    }
+    for (old, new) in diff.changed_components() {
+        info!("Component {} changed in configure", new.uuid());
+        // Previous statuspage component is no longer reported, same as removed one
+        if !old.need_push() || (old.report_id(), old.page()) == (new.report_id(), new.page()) {
+            continue;
+        }
+        upstream
+            .set_component_status(
+                old.report_id(),
+                old.page(),
+                statuspagelib::ComponentStatus::Operational,
+            )
+            .await
+            .map_err(|e| {
+                warn!(
+                    "Reset previous status of changed component {} error: {:?}",
+                    old.uuid(),
+                    e
+                )
+            })
+            .ok();
+    }
+    for component in diff
+        .added_components()
+        .iter()
+        .chain(diff.changed_components().iter().map(|(_, new)| new))
+    {
+        let result = match pool.acquire().await {
+            Ok(mut conn) => database::upsert_component(&mut conn, component).await,
+            Err(e) => Err(e.into()),
+        };
+        result.map_err(|e| warn!("{:?}", e)).ok();
+    }
+    if diff.changed_oauth() {
+        info!("Statuspage oauth changed, rebuild upstreams");
+    }
+    if diff.changed_server() {
+        warn!("Server configure changed, listen address and database location require restart to apply");
+    }
+}
+
+#[cfg(unix)]
+fn spawn_reload_handler(
+    config_file: String,
+    mut config: Configure,
+    tags: Option<Vec<String>>,
+    pool: SqlitePool,
+    upstream: Arc<ReloadableUpstream>,
+    state: AppState,
+) {
+    tokio::spawn(async move {
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).unwrap();
+        while hangup.recv().await.is_some() {
+            info!("Recv SIGHUP, reload configure from {}", &config_file);
+            let mut new_config = match Configure::init_from_path(&config_file).await {
+                Ok(new_config) => new_config,
+                Err(e) => {
+                    warn!("Reload configure error, keep current configure: {:?}", e);
+                    continue;
+                }
+            };
+            if let Some(ref tags) = tags {
+                new_config.retain_components_by_tags(tags);
+            }
+            if let Err(e) = new_config.check_component_limit() {
+                warn!("Reload configure error, keep current configure: {:?}", e);
+                continue;
+            }
+            // Rebuild upstreams, so new credentials and component names are applied
+            let statuspage = match StatusPageUpstream::from_configure(&new_config) {
+                Ok(statuspage) => statuspage,
+                Err(e) => {
+                    warn!(
+                        "Build statuspage upstream error, keep current configure: {:?}",
+                        e
+                    );
+                    continue;
+                }
+            };
+            if let Some(ref statuspage) = statuspage {
+                resolve_component_names(&mut new_config, statuspage).await;
+            }
+            let new_upstream = match build_upstream(&new_config) {
+                Ok(new_upstream) => new_upstream,
+                Err(e) => {
+                    warn!("Build upstreams error, keep current configure: {:?}", e);
+                    continue;
+                }
+            };
+            let diff = Configure::diff(&config, &new_config);
+            apply_config_diff(&diff, &pool, upstream.as_ref()).await;
+            upstream.replace(Arc::from(new_upstream)).await;
+            state.update_components(&new_config).await;
+            config = new_config;
+        }
+    });
+}
 
-    let router = make_router(check_database(&config, sqlite_connection).await?, upstream);
+async fn async_main(
+    config_file: String,
+    mut config: Configure,
+    tags: Option<Vec<String>>,
+    debug: bool,
+) -> anyhow::Result<()> {
+    if let Some(ref tags) = tags {
+        config.retain_components_by_tags(tags);
+        info!(
+            "Filter components by tags {:?}, {} component(s) left",
+            tags,
+            config.components().len()
+        );
+    }
+    config.check_component_limit()?;
+
+    // Resolve names before building upstreams, which keep component names for messages
+    if let Some(statuspage) = StatusPageUpstream::from_configure(&config)? {
+        resolve_component_names(&mut config, &statuspage).await;
+    }
+    let upstream = build_upstream(&config)?;
+
+    let mut sqlite_connection = open_database(&config.server().database_location()).await?;
+    database::check_json_support(&mut sqlite_connection).await?;
+
+    check_database(&config, &mut sqlite_connection).await?;
+    sqlite_connection.close().await?;
+    let pool = open_pool(&config.server().database_location()).await?;
+    let upstream = Arc::new(ReloadableUpstream::new(Arc::from(upstream)));
+    let (events, _) = broadcast::channel(16);
+
+    if config.statuspage().enabled() && !config.statuspage().maintenance_templates().is_empty() {
+        if let Some(statuspage) = StatusPageUpstream::from_configure(&config)? {
+            maintenance::spawn_maintenance_scheduler(
+                config.statuspage().maintenance_templates().to_vec(),
+                statuspage,
+                pool.clone(),
+            );
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_port) = config.server().grpc_port() {
+        let service = grpc::StatusServiceImpl::new(
+            pool.clone(),
+            upstream.clone(),
+            events.clone(),
+            config
+                .server()
+                .status_aliases()
+                .cloned()
+                .unwrap_or_default(),
+        );
+        let bind = format!("{}:{}", config.server().addr(), grpc_port);
+        let addr = bind.parse().unwrap();
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(service.into_server())
+                .serve(addr)
+                .await
+                .map_err(|e| error!("gRPC server error: {:?}", e))
+                .ok();
+        });
+    }
+
+    tokio::spawn({
+        let pool = pool.clone();
+        let retention =
+            Duration::from_secs(config.server().audit_retention_days().saturating_mul(86400));
+        async move {
+            let mut interval = tokio::time::interval(AUDIT_PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let before = get_current_timestamp().saturating_sub(retention.as_secs());
+                let result = match pool.acquire().await {
+                    Ok(mut conn) => database::audit::prune(&mut conn, before).await,
+                    Err(e) => Err(e.into()),
+                };
+                match result {
+                    Ok(rows) if rows > 0 => info!("Prune {} audit log entries", rows),
+                    Ok(_) => {}
+                    Err(e) => error!("Prune audit log error: {:?}", e),
+                }
+            }
+        }
+    });
+
+    // Report not ready until database and upstreams are set up and server is listening
+    let ready = Arc::new(AtomicBool::new(false));
+    let state = AppState::new(
+        &config,
+        pool.clone(),
+        upstream.clone(),
+        events,
+        ready.clone(),
+    );
+
+    #[cfg(unix)]
+    spawn_reload_handler(
+        config_file,
+        config.clone(),
+        tags,
+        pool,
+        upstream,
+        state.clone(),
+    );
+    #[cfg(not(unix))]
+    let _ = (config_file, tags, pool, upstream);
+
+    let router = make_router(&config, state, debug);
     let bind = format!("{}:{}", config.server().addr(), config.server().port());
     let server_handler = axum_server::Handle::new();
     let server = tokio::spawn(
         axum_server::bind(bind.parse().unwrap())
             .handle(server_handler.clone())
-            .serve(router.into_make_service()),
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>()),
     );
+    ready.store(true, Ordering::Relaxed);
 
     tokio::select! {
         _ = async {
             tokio::signal::ctrl_c().await.unwrap();
             info!("Recv Control-C send graceful shutdown command.");
+            ready.store(false, Ordering::Relaxed);
             server_handler.graceful_shutdown(None);
             tokio::signal::ctrl_c().await.unwrap();
             warn!("Force to exit!");
             std::process::exit(137)
         } => {
         },
+        _ = async {
+            wait_terminate_signal().await;
+            info!("Recv SIGTERM send graceful shutdown command.");
+            ready.store(false, Ordering::Relaxed);
+            server_handler.graceful_shutdown(None);
+            std::future::pending::<()>().await
+        } => {
+        },
         _ = server => {
         }
     }
@@ -136,11 +531,19 @@ async fn async_main(config_file: &str) -> anyhow::Result<()> {
 }
 
 #[cfg(feature = "spdlog-rs")]
-fn init_spdlog_file(log_target: &str, is_debug: bool) {
-    let file_sink = std::sync::Arc::new(FileSink::new(log_target, false).unwrap_or_else(|e| {
-        eprintln!("Got error while create log file: {:?}", e);
-        std::process::exit(1);
-    }));
+fn init_spdlog_file(log_target: &str, debug_level: u8, max_size: u64, max_files: usize) {
+    let file_sink = std::sync::Arc::new(
+        RotatingFileSink::new(
+            log_target,
+            RotationPolicy::FileSize(max_size),
+            max_files,
+            false,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Got error while create log file: {:?}", e);
+            std::process::exit(1);
+        }),
+    );
     // stdout & stderr
     let default_sinks = default_logger().sinks().to_owned();
     let logger = std::sync::Arc::new(
@@ -149,10 +552,10 @@ fn init_spdlog_file(log_target: &str, is_debug: bool) {
             .sink(file_sink)
             .build(),
     );
-    let level_filter = if is_debug {
-        LevelFilter::MoreSevereEqual(Level::Debug)
-    } else {
-        LevelFilter::MoreSevereEqual(Level::Info)
+    let level_filter = match debug_level {
+        0 => LevelFilter::MoreSevereEqual(Level::Info),
+        1 => LevelFilter::MoreSevereEqual(Level::Debug),
+        _ => LevelFilter::MoreSevereEqual(Level::Trace),
     };
     logger.set_level_filter(level_filter);
 
@@ -160,12 +563,24 @@ fn init_spdlog_file(log_target: &str, is_debug: bool) {
 }
 
 #[cfg(feature = "log4rs")]
-fn init_log4rs(log_target: &str, debug: bool) -> anyhow::Result<()> {
-    let log_file_requests = log4rs::append::file::FileAppender::builder()
+fn init_log4rs(
+    log_target: &str,
+    debug_level: u8,
+    max_size: u64,
+    max_files: u32,
+) -> anyhow::Result<()> {
+    use log4rs::append::rolling_file::policy::compound::{
+        roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+    };
+    use log4rs::append::rolling_file::RollingFileAppender;
+
+    let roller = FixedWindowRoller::builder().build(&format!("{}.{{}}", log_target), max_files)?;
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(max_size)), Box::new(roller));
+    let log_file_requests = RollingFileAppender::builder()
         .encoder(Box::new(log4rs::encode::pattern::PatternEncoder::new(
             "{d(%Y-%m-%d %H:%M:%S)}- {h({l})} - {m}{n}",
         )))
-        .build(log_target);
+        .build(log_target, Box::new(policy));
     if let Err(ref e) = log_file_requests {
         eprintln!("Got error while create log file: {:?}", e);
     }
@@ -176,10 +591,10 @@ fn init_log4rs(log_target: &str, debug: bool) -> anyhow::Result<()> {
         .build(
             log4rs::config::Root::builder()
                 .appender("logfile")
-                .build(if debug {
-                    log::LevelFilter::Debug
-                } else {
-                    log::LevelFilter::Info
+                .build(match debug_level {
+                    0 => log::LevelFilter::Info,
+                    1 => log::LevelFilter::Debug,
+                    _ => log::LevelFilter::Trace,
                 }),
         )
         .unwrap();
@@ -193,17 +608,46 @@ fn main() -> anyhow::Result<()> {
         .args(&[
             arg!(--config [FILE] "Specify configure file"),
             arg!(--logfile [LOGFILE] "Specify log file out instead of output to stdout"),
-            arg!(-d --debug ... "turns debug logging"),
+            arg!(-d --debug ... "turns debug logging, use twice to also log request body"),
             arg!(--cache [CACHEFILE] "Specify cache file location"),
+            arg!(--tags [TAGS] "Only handle components with any of these tags (comma separated)"),
+            arg!(--"list-components" "Print components stored in database and exit"),
+            arg!(--"generate-schema" "Print JSON schema of configure file and exit"),
+            arg!(--"reset-status" <UUID> "Reset status of component to unknown and exit")
+                .conflicts_with("all"),
+            arg!(--all "Reset status of all components to unknown and exit")
+                .conflicts_with("reset-status"),
+            arg!(--"import-status" [FILE] "Import component status from JSON file and exit"),
+            arg!(--"log-max-size-mb" [SIZE] "Rotate log file when it exceeds this size (in MiB)")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("100"),
+            arg!(--"log-max-files" [COUNT] "Number of rotated log files to keep")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("5"),
         ])
         .get_matches();
 
+    if matches.get_flag("generate-schema") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schemars::schema_for!(Configure))?
+        );
+        return Ok(());
+    }
+
     #[cfg(feature = "spdlog-rs")]
     init_log_crate_proxy().expect("Init log crate got error");
     if let Some(log_target) = matches.get_one::<String>("logfile") {
+        let max_size = matches.get_one::<u64>("log-max-size-mb").unwrap() * 1024 * 1024;
+        let max_files = *matches.get_one::<u32>("log-max-files").unwrap();
         #[cfg(feature = "spdlog-rs")]
-        init_spdlog_file(log_target, matches.contains_id("debug"));
-        init_log4rs(log_target, matches.contains_id("debug"))?;
+        init_spdlog_file(
+            log_target,
+            matches.get_count("debug"),
+            max_size,
+            max_files as usize,
+        );
+        init_log4rs(log_target, matches.get_count("debug"), max_size, max_files)?;
     } else {
         #[cfg(feature = "spdlog-rs")]
         default_logger().set_level_filter(LevelFilter::MoreSevereEqual(Level::Debug));
@@ -214,15 +658,81 @@ fn main() -> anyhow::Result<()> {
         info!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     }
 
-    tokio::runtime::Builder::new_current_thread()
+    let tags = match matches.get_one::<String>("tags") {
+        Some(tags) => {
+            let tags: Vec<String> = tags
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            // Empty filter would drop every component
+            if tags.is_empty() {
+                return Err(anyhow!("--tags should contain at least one tag"));
+            }
+            Some(tags)
+        }
+        None => None,
+    };
+
+    let config_file = matches
+        .get_one::<String>("config")
+        .map(|s| s.as_str())
+        .unwrap_or("config/default.toml");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let config = runtime
+        .block_on(Configure::init_from_path(config_file))
+        .map_err(|e| anyhow!("Read configure file failure: {:?}", e))?;
+
+    if matches.get_flag("list-components") {
+        return runtime.block_on(list_components(&config));
+    }
+    if let Some(uuid) = matches.get_one::<String>("reset-status") {
+        return runtime.block_on(async {
+            let mut conn = open_database(&config.server().database_location()).await?;
+            database::reset_component_status(&mut conn, Some(uuid)).await
+        });
+    }
+    if matches.get_flag("all") {
+        return runtime.block_on(async {
+            let mut conn = open_database(&config.server().database_location()).await?;
+            database::reset_component_status(&mut conn, None).await
+        });
+    }
+    if let Some(file) = matches.get_one::<String>("import-status") {
+        return runtime.block_on(async {
+            let mut conn = open_database(&config.server().database_location()).await?;
+            let summary = database::import_status(&mut conn, file).await?;
+            println!(
+                "Import status from {}: {} updated, {} skipped",
+                file,
+                summary.updated(),
+                summary.skipped()
+            );
+            Ok(())
+        });
+    }
+    drop(runtime);
+
+    let mut runtime_builder = match config.server().worker_threads() {
+        Some(worker_threads) if worker_threads > 1 => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.worker_threads(worker_threads);
+            builder
+        }
+        _ => tokio::runtime::Builder::new_current_thread(),
+    };
+    runtime_builder
         .enable_all()
         .build()
         .unwrap()
         .block_on(async_main(
-            matches
-                .get_one::<String>("config")
-                .map(|s| s.as_str())
-                .unwrap_or("config/default.toml"),
+            config_file.to_string(),
+            config,
+            tags,
+            matches.get_count("debug") > 0,
         ))?;
     Ok(())
 }
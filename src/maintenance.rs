@@ -0,0 +1,97 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::configure::MaintenanceTemplate;
+use crate::database::get_current_timestamp;
+use crate::statuspagelib::StatusPageUpstream;
+#[cfg(any(feature = "env_logger", feature = "log4rs"))]
+use log::{error, info};
+#[cfg(feature = "spdlog-rs")]
+use spdlog::prelude::*;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn check_maintenance_templates(
+    templates: &[MaintenanceTemplate],
+    upstream: &StatusPageUpstream,
+    pool: &SqlitePool,
+) -> anyhow::Result<()> {
+    let current_time = get_current_timestamp() as i64;
+    for template in templates {
+        let (scheduled_for, scheduled_until) = match (
+            template.scheduled_for_timestamp(),
+            template.scheduled_until_timestamp(),
+        ) {
+            (Ok(scheduled_for), Ok(scheduled_until)) => (scheduled_for, scheduled_until),
+            (Err(e), _) | (_, Err(e)) => {
+                error!(
+                    "Parse schedule time of maintenance {:?} error: {:?}",
+                    template.name(),
+                    e
+                );
+                continue;
+            }
+        };
+        if scheduled_for > current_time || scheduled_until <= current_time {
+            continue;
+        }
+
+        let created =
+            sqlx::query_as::<_, (i32,)>(r#"SELECT 1 FROM "maintenance_history" WHERE "name" = ?"#)
+                .bind(template.name())
+                .fetch_optional(pool)
+                .await?;
+        if created.is_some() {
+            continue;
+        }
+
+        if let Err(e) = upstream.create_maintenance(template).await {
+            error!(
+                "Create scheduled maintenance {:?} error: {:?}",
+                template.name(),
+                e
+            );
+            continue;
+        }
+        sqlx::query(r#"INSERT INTO "maintenance_history" VALUES (?, ?)"#)
+            .bind(template.name())
+            .bind(current_time)
+            .execute(pool)
+            .await?;
+        info!("Create scheduled maintenance {:?}", template.name());
+    }
+    Ok(())
+}
+
+pub fn spawn_maintenance_scheduler(
+    templates: Vec<MaintenanceTemplate>,
+    upstream: StatusPageUpstream,
+    pool: SqlitePool,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_maintenance_templates(&templates, &upstream, &pool).await {
+                error!("Check maintenance templates error: {:?}", e);
+            }
+        }
+    })
+}
@@ -16,19 +16,28 @@
  */
 
 const UPSTREAM_URL: &str = "https://api.statuspage.io/";
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
 
 mod v1 {
-    use super::UPSTREAM_URL;
+    use super::{MAX_RETRY_DELAY, UPSTREAM_URL};
+    use crate::configure::MaintenanceTemplate;
     use crate::datastructures::{ServerLastStatus, UpstreamTrait};
     use crate::Configure;
     use anyhow::anyhow;
-    use reqwest::header::{HeaderMap, HeaderValue};
-    use reqwest::Client;
+    #[cfg(any(feature = "env_logger", feature = "log4rs"))]
+    use log::warn;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::{Client, RequestBuilder, Response, StatusCode};
+    use serde_derive::Deserialize;
     use serde_json::json;
+    #[cfg(feature = "spdlog-rs")]
+    use spdlog::prelude::*;
+    use std::collections::HashMap;
     use std::fmt::Formatter;
     use std::time::Duration;
 
     #[allow(dead_code)]
+    #[derive(Clone, Copy, Debug)]
     pub enum ComponentStatus {
         Operational,
         UnderMaintenance,
@@ -96,9 +105,105 @@ mod v1 {
         }
     }
 
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct ComponentResponse {
+        name: String,
+    }
+
+    impl ComponentResponse {
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct ComponentSummary {
+        id: String,
+        name: String,
+        status: String,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct RetryPolicy {
+        max_retries: u32,
+        backoff: Duration,
+    }
+
+    impl RetryPolicy {
+        pub fn new(max_retries: u32, backoff_ms: u64) -> Self {
+            Self {
+                max_retries,
+                backoff: Duration::from_millis(backoff_ms),
+            }
+        }
+
+        fn retry_delay(&self, attempt: u32, response: &Response) -> Option<Duration> {
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                return Some(
+                    response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse().ok())
+                        .map(Duration::from_secs)
+                        .unwrap_or(self.backoff),
+                );
+            }
+            if response.status().is_server_error() {
+                return Some(
+                    self.backoff
+                        .checked_mul(2u32.saturating_pow(attempt))
+                        .unwrap_or(Duration::MAX),
+                );
+            }
+            None
+        }
+
+        pub async fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+            let mut attempt = 0;
+            loop {
+                let response = request
+                    .try_clone()
+                    .ok_or_else(|| anyhow!("Request can not be retried"))?
+                    .send()
+                    .await?;
+                let delay = match self.retry_delay(attempt, &response) {
+                    Some(delay) => delay,
+                    None => return Ok(response),
+                };
+                if attempt >= self.max_retries {
+                    return Err(anyhow!(
+                        "Max retries exceeded, last status: {}",
+                        response.status()
+                    ));
+                }
+                // Do not block caller for a long time, e.g. Retry-After: 86400
+                if delay > MAX_RETRY_DELAY {
+                    return Err(anyhow!(
+                        "Got status {} from upstream, retry delay {:?} exceeds {:?}",
+                        response.status(),
+                        delay,
+                        MAX_RETRY_DELAY
+                    ));
+                }
+                warn!(
+                    "Got status {} from upstream, retry in {:?} ({}/{})",
+                    response.status(),
+                    delay,
+                    attempt + 1,
+                    self.max_retries
+                );
+                attempt += 1;
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
     #[derive(Debug, Clone)]
     pub struct StatusPageUpstream {
         client: Client,
+        retry_policy: RetryPolicy,
     }
 
     impl StatusPageUpstream {
@@ -113,17 +218,68 @@ mod v1 {
             map.insert(
                 "Authorization",
                 HeaderValue::from_str(cfg.statuspage().oauth())
-                    .expect("OAuth Header value parse error"),
+                    .map_err(|e| anyhow!("OAuth header value parse error: {:?}", e))?,
             );
             Ok(Some(Self {
                 client: reqwest::ClientBuilder::new()
                     .default_headers(map.clone())
-                    .timeout(Duration::from_secs(10))
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
                     .build()
                     .unwrap(),
+                retry_policy: RetryPolicy::new(
+                    cfg.statuspage().max_retries(),
+                    cfg.statuspage().retry_backoff_ms(),
+                ),
             }))
         }
 
+        pub async fn create_maintenance(
+            &self,
+            template: &MaintenanceTemplate,
+        ) -> anyhow::Result<()> {
+            let payload = json!({
+                "incident": {
+                    "name": template.name(),
+                    "status": "scheduled",
+                    "body": template.body(),
+                    "scheduled_for": template.scheduled_for(),
+                    "scheduled_until": template.scheduled_until(),
+                    "scheduled_auto_in_progress": true,
+                    "scheduled_auto_completed": true,
+                    "component_ids": template.component_ids(),
+                }
+            });
+            self.client
+                .post(format!(
+                    "{basic_url}v1/pages/{page_id}/incidents",
+                    basic_url = UPSTREAM_URL,
+                    page_id = template.page()
+                ))
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+
+        pub async fn get_all_components(
+            &self,
+            page: &str,
+        ) -> anyhow::Result<Vec<ComponentSummary>> {
+            Ok(self
+                .client
+                .get(format!(
+                    "{basic_url}v1/pages/{page_id}/components",
+                    basic_url = UPSTREAM_URL,
+                    page_id = page
+                ))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?)
+        }
+
         pub fn build_request_url(&self, component_id: &str, page: &str) -> String {
             format!(
                 "{basic_url}v1/pages/{page_id}/components/{component_id}",
@@ -144,22 +300,74 @@ mod v1 {
             Ok(())
         }
 
+        async fn get_component_name(&self, component: &str, page: &str) -> anyhow::Result<String> {
+            let response = self
+                .client
+                .get(self.build_request_url(component, page))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<ComponentResponse>()
+                .await?;
+            Ok(response.name().to_string())
+        }
+
+        async fn get_component_names(&self, page: &str) -> anyhow::Result<HashMap<String, String>> {
+            Ok(self
+                .get_all_components(page)
+                .await?
+                .into_iter()
+                .map(|component| (component.id, component.name))
+                .collect())
+        }
+
         async fn set_component_status(
             &self,
             component: &str,
             page: &str,
             status: ComponentStatus,
         ) -> anyhow::Result<()> {
+            // Component is not mapped to statuspage, other upstreams still handle it
+            if component.is_empty() || page.is_empty() {
+                return Ok(());
+            }
             let payload = json!({
                 "component": {
                     "status": status.to_string()
                 }
             });
-            self.client
-                .patch(self.build_request_url(component, page))
-                .json(&payload)
-                .send()
-                .await?;
+            self.retry_policy
+                .send(
+                    self.client
+                        .patch(self.build_request_url(component, page))
+                        .json(&payload),
+                )
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+
+        async fn set_raw_status(
+            &self,
+            component: &str,
+            page: &str,
+            payload: serde_json::Value,
+        ) -> anyhow::Result<()> {
+            // Other fields are passed through, but statuspage rejects whole request on bad status
+            if let Some(status) = payload.get("status") {
+                let status = status
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Field \"status\" should be string, got {}", status))?;
+                ComponentStatus::try_from(status)?;
+            }
+            self.retry_policy
+                .send(
+                    self.client
+                        .patch(self.build_request_url(component, page))
+                        .json(&json!({ "component": payload })),
+                )
+                .await?
+                .error_for_status()?;
             Ok(())
         }
     }
@@ -167,3 +375,72 @@ mod v1 {
 
 pub use v1::ComponentStatus;
 pub use v1::StatusPageUpstream;
+
+#[cfg(test)]
+mod tests {
+    use super::StatusPageUpstream;
+    use crate::datastructures::UpstreamTrait;
+    use crate::Configure;
+    use serde_json::json;
+
+    fn build_configure(enabled: bool) -> Configure {
+        toml::from_str(&format!(
+            r#"
+            components = []
+
+            [statuspage]
+            enabled = {}
+            oauth = "OAuth token"
+
+            [server]
+            addr = "127.0.0.1"
+            port = 41132
+            public_status_page = false
+            "#,
+            enabled
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_configure_disabled() {
+        assert!(StatusPageUpstream::from_configure(&build_configure(false))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_from_configure_enabled() {
+        assert!(StatusPageUpstream::from_configure(&build_configure(true))
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_set_raw_status_rejects_invalid_status() {
+        let upstream = StatusPageUpstream::from_configure(&build_configure(true))
+            .unwrap()
+            .unwrap();
+        for payload in [json!({"status": "broken"}), json!({"status": 0})] {
+            assert!(upstream
+                .set_raw_status("component", "page", payload)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_unmapped_component() {
+        let upstream = StatusPageUpstream::from_configure(&build_configure(true))
+            .unwrap()
+            .unwrap();
+        upstream
+            .set_component_status(
+                "00000000000000000000000000000001",
+                "",
+                super::ComponentStatus::MajorOutage,
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+pub mod betterstack;
+pub mod discord;
+#[cfg(feature = "freshping")]
+pub mod freshping;
+pub mod matrix;
+#[cfg(feature = "pagerduty")]
+pub mod pagerduty;
@@ -0,0 +1,176 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod v1 {
+    use crate::datastructures::UpstreamTrait;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+    use anyhow::anyhow;
+    #[cfg(any(feature = "env_logger", feature = "log4rs"))]
+    use log::debug;
+    use reqwest::Client;
+    #[cfg(feature = "spdlog-rs")]
+    use spdlog::prelude::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone)]
+    pub struct BetterStackUpstream {
+        client: Client,
+        /// Map id passed to upstreams to BetterStack heartbeat url
+        heartbeat_urls: HashMap<String, String>,
+        default_heartbeat_url: Option<String>,
+        down_url: Option<String>,
+    }
+
+    impl BetterStackUpstream {
+        pub fn from_configure(cfg: &Configure) -> anyhow::Result<Option<BetterStackUpstream>> {
+            if !cfg.betterstack().enabled() {
+                return Ok(None);
+            }
+            let heartbeat_urls: HashMap<String, String> = cfg
+                .components()
+                .iter()
+                .filter_map(|component| {
+                    component
+                        .betterstack_heartbeat_url()
+                        .map(|url| (component.upstream_id().to_string(), url.to_string()))
+                })
+                .collect();
+            if heartbeat_urls.is_empty() && cfg.betterstack().heartbeat_url().is_none() {
+                return Err(anyhow!("Heartbeat url field is empty"));
+            }
+            Ok(Some(Self {
+                client: reqwest::ClientBuilder::new()
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
+                    .build()
+                    .unwrap(),
+                heartbeat_urls,
+                default_heartbeat_url: cfg.betterstack().heartbeat_url().map(|s| s.to_string()),
+                down_url: cfg.betterstack().down_url().map(|s| s.to_string()),
+            }))
+        }
+
+        pub fn heartbeat_url(&self, component: &str) -> Option<&str> {
+            self.heartbeat_urls
+                .get(component)
+                .or(self.default_heartbeat_url.as_ref())
+                .map(|url| url.as_str())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for BetterStackUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            _page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            let url = match status {
+                ComponentStatus::Operational => match self.heartbeat_url(component) {
+                    Some(heartbeat_url) => heartbeat_url,
+                    None => {
+                        debug!("BetterStack heartbeat url not set, skip {}", component);
+                        return Ok(());
+                    }
+                },
+                _ => match self.down_url {
+                    Some(ref down_url) => down_url,
+                    None => {
+                        debug!(
+                            "BetterStack down url not set, skip {} status {}",
+                            component, status
+                        );
+                        return Ok(());
+                    }
+                },
+            };
+            self.client.get(url).send().await?.error_for_status()?;
+            Ok(())
+        }
+
+        // BetterStack expects heartbeat periodically, not only when status changed
+        async fn heartbeat(&self, component: &str, page: &str) -> anyhow::Result<()> {
+            self.set_component_status(component, page, ComponentStatus::Operational)
+                .await
+        }
+    }
+}
+
+pub use v1::BetterStackUpstream;
+
+#[cfg(test)]
+mod tests {
+    use super::BetterStackUpstream;
+    use crate::Configure;
+
+    fn build_configure(heartbeat_url: &str) -> Configure {
+        toml::from_str(&format!(
+            r#"
+            components = [
+                {{ uuid = "00000000000000000000000000000001", name = "a", identity_id = "component", page = "page", betterstack_heartbeat_url = "https://uptime.betterstack.com/api/v1/heartbeat/a" }},
+                {{ uuid = "00000000000000000000000000000002", name = "b" }},
+            ]
+
+            [statuspage]
+            enabled = false
+            oauth = ""
+
+            [betterstack]
+            enabled = true
+            heartbeat_url = "{}"
+
+            [server]
+            addr = "127.0.0.1"
+            port = 41132
+            public_status_page = false
+            "#,
+            heartbeat_url
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_heartbeat_url_per_component() {
+        let upstream = BetterStackUpstream::from_configure(&build_configure(
+            "https://uptime.betterstack.com/api/v1/heartbeat/default",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            upstream.heartbeat_url("component"),
+            Some("https://uptime.betterstack.com/api/v1/heartbeat/a")
+        );
+        assert_eq!(
+            upstream.heartbeat_url("00000000000000000000000000000002"),
+            Some("https://uptime.betterstack.com/api/v1/heartbeat/default")
+        );
+
+        let upstream = BetterStackUpstream::from_configure(&build_configure(""))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            upstream.heartbeat_url("00000000000000000000000000000002"),
+            None
+        );
+    }
+}
@@ -0,0 +1,149 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+const DEFAULT_USERNAME: &str = "Status Upstream";
+const MAX_RETRY_AFTER_SECS: f64 = 30.0;
+
+mod v1 {
+    use super::{DEFAULT_USERNAME, MAX_RETRY_AFTER_SECS};
+    use crate::configure::Component;
+    use crate::datastructures::UpstreamTrait;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+    use anyhow::anyhow;
+    #[cfg(any(feature = "env_logger", feature = "log4rs"))]
+    use log::warn;
+    use reqwest::{Client, StatusCode};
+    use serde_json::json;
+    #[cfg(feature = "spdlog-rs")]
+    use spdlog::prelude::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone)]
+    pub struct DiscordUpstream {
+        client: Client,
+        webhook_url: String,
+        username: String,
+        avatar_url: Option<String>,
+        components: HashMap<String, Component>,
+    }
+
+    impl DiscordUpstream {
+        pub fn from_configure(cfg: &Configure) -> anyhow::Result<Option<DiscordUpstream>> {
+            if !cfg.discord().enabled() {
+                return Ok(None);
+            }
+            if cfg.discord().webhook_url().is_empty() {
+                return Err(anyhow!("Webhook url field is empty"));
+            }
+            Ok(Some(Self {
+                client: reqwest::ClientBuilder::new()
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
+                    .build()
+                    .unwrap(),
+                webhook_url: cfg.discord().webhook_url().to_string(),
+                username: cfg
+                    .discord()
+                    .username()
+                    .unwrap_or(DEFAULT_USERNAME)
+                    .to_string(),
+                avatar_url: cfg.discord().avatar_url().map(|s| s.to_string()),
+                components: cfg.components_by_upstream_id(),
+            }))
+        }
+
+        pub fn build_payload(
+            &self,
+            component: &str,
+            page: &str,
+            status: &ComponentStatus,
+        ) -> serde_json::Value {
+            let color = match status {
+                ComponentStatus::Operational => 0x00cc00,
+                ComponentStatus::UnderMaintenance => 0x0088cc,
+                ComponentStatus::DegradedPerformance => 0xcccc00,
+                ComponentStatus::PartialOutage => 0xff8800,
+                ComponentStatus::MajorOutage => 0xcc0000,
+            };
+            // Fallback to upstream id if component is not found in configure
+            let name = self
+                .components
+                .get(component)
+                .map(|component| component.display_name())
+                .unwrap_or(component);
+            json!({
+                "username": self.username,
+                "avatar_url": self.avatar_url,
+                "embeds": [{
+                    "title": "Component status changed",
+                    "color": color,
+                    "fields": [
+                        {"name": "Component", "value": name, "inline": true},
+                        {"name": "Page", "value": page, "inline": true},
+                        {"name": "Status", "value": status.to_string(), "inline": false},
+                    ],
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                }]
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for DiscordUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            let payload = self.build_payload(component, page, &status);
+            let mut response = self
+                .client
+                .post(&self.webhook_url)
+                .json(&payload)
+                .send()
+                .await?;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .unwrap_or(1.0)
+                    .min(MAX_RETRY_AFTER_SECS);
+                warn!("Discord webhook rate limited, retry after {}s", retry_after);
+                tokio::time::sleep(Duration::from_secs_f64(retry_after)).await;
+                response = self
+                    .client
+                    .post(&self.webhook_url)
+                    .json(&payload)
+                    .send()
+                    .await?;
+            }
+            response.error_for_status()?;
+            Ok(())
+        }
+    }
+}
+
+pub use v1::DiscordUpstream;
@@ -0,0 +1,110 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod v1 {
+    use crate::datastructures::UpstreamTrait;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+    use anyhow::anyhow;
+    use reqwest::Client;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone)]
+    pub struct FreshpingUpstream {
+        client: Client,
+        subdomain: String,
+        api_key: String,
+        /// Map id passed to upstreams to Freshping check id
+        check_ids: HashMap<String, String>,
+    }
+
+    impl FreshpingUpstream {
+        pub fn from_configure(cfg: &Configure) -> anyhow::Result<Option<FreshpingUpstream>> {
+            if !cfg.freshping().enabled() {
+                return Ok(None);
+            }
+            if cfg.freshping().subdomain().is_empty() {
+                return Err(anyhow!("Subdomain field is empty"));
+            }
+            if cfg.freshping().api_key().is_empty() {
+                return Err(anyhow!("Api key field is empty"));
+            }
+            Ok(Some(Self {
+                client: reqwest::ClientBuilder::new()
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
+                    .build()
+                    .unwrap(),
+                subdomain: cfg.freshping().subdomain().to_string(),
+                api_key: cfg.freshping().api_key().to_string(),
+                check_ids: cfg
+                    .components()
+                    .iter()
+                    .filter_map(|component| {
+                        component.freshping_check_id().map(|check_id| {
+                            (component.upstream_id().to_string(), check_id.to_string())
+                        })
+                    })
+                    .collect(),
+            }))
+        }
+
+        pub fn build_request_url(&self, check_id: &str) -> String {
+            format!(
+                "https://{}.freshping.io/api/checks/{}",
+                self.subdomain, check_id
+            )
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for FreshpingUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            _page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            // Pause check while outage to prevent false incident creation
+            let paused = match status {
+                ComponentStatus::MajorOutage => true,
+                ComponentStatus::Operational => false,
+                _ => return Ok(()),
+            };
+            // Component without check id is not monitored by Freshping
+            let check_id = match self.check_ids.get(component) {
+                Some(check_id) => check_id,
+                None => return Ok(()),
+            };
+            self.client
+                .put(self.build_request_url(check_id))
+                .basic_auth(&self.api_key, Some(&self.subdomain))
+                .json(&json!({ "paused": paused }))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+    }
+}
+
+pub use v1::FreshpingUpstream;
@@ -0,0 +1,229 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod v1 {
+    use crate::configure::Component;
+    use crate::datastructures::UpstreamTrait;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+    use anyhow::anyhow;
+    use reqwest::{Client, Url};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fmt::{Debug, Formatter};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    pub struct MatrixUpstream {
+        client: Client,
+        homeserver_url: Url,
+        access_token: String,
+        room_id: String,
+        txn_id: AtomicU64,
+        components: HashMap<String, Component>,
+    }
+
+    // Access token grants full access to the account, keep it out of debug output
+    impl Debug for MatrixUpstream {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("MatrixUpstream")
+                .field("client", &self.client)
+                .field("homeserver_url", &self.homeserver_url)
+                .field("access_token", &"<redacted>")
+                .field("room_id", &self.room_id)
+                .field("txn_id", &self.txn_id)
+                .field("components", &self.components)
+                .finish()
+        }
+    }
+
+    impl MatrixUpstream {
+        pub fn from_configure(cfg: &Configure) -> anyhow::Result<Option<MatrixUpstream>> {
+            if !cfg.matrix().enabled() {
+                return Ok(None);
+            }
+            if cfg.matrix().access_token().is_empty() || cfg.matrix().room_id().is_empty() {
+                return Err(anyhow!("Access token or room id field is empty"));
+            }
+            let homeserver_url = Url::parse(cfg.matrix().homeserver_url())
+                .map_err(|e| anyhow!("Parse homeserver url error: {:?}", e))?;
+            if homeserver_url.cannot_be_a_base() {
+                return Err(anyhow!("Homeserver url can not be a base url"));
+            }
+            // Matrix deduplicates events by transaction id per access token, start from current
+            // time so that transaction ids will not be reused after restart
+            let start = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_millis() as u64;
+            Ok(Some(Self {
+                client: reqwest::ClientBuilder::new()
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
+                    .build()
+                    .unwrap(),
+                homeserver_url,
+                access_token: cfg.matrix().access_token().to_string(),
+                room_id: cfg.matrix().room_id().to_string(),
+                txn_id: AtomicU64::new(start),
+                components: cfg.components_by_upstream_id(),
+            }))
+        }
+
+        pub fn build_request_url(&self, txn_id: u64) -> Url {
+            let mut url = self.homeserver_url.clone();
+            url.path_segments_mut()
+                .expect("Homeserver url is checked while building")
+                .pop_if_empty()
+                .extend([
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "rooms",
+                    &self.room_id,
+                    "send",
+                    "m.room.message",
+                    &txn_id.to_string(),
+                ]);
+            url
+        }
+
+        pub fn build_payload(
+            &self,
+            component: &str,
+            page: &str,
+            status: &ComponentStatus,
+        ) -> serde_json::Value {
+            let color = match status {
+                ComponentStatus::Operational => "#00cc00",
+                ComponentStatus::UnderMaintenance => "#0088cc",
+                ComponentStatus::DegradedPerformance => "#cccc00",
+                ComponentStatus::PartialOutage => "#ff8800",
+                ComponentStatus::MajorOutage => "#cc0000",
+            };
+            // Fallback to upstream id if component is not found in configure
+            let name = self
+                .components
+                .get(component)
+                .map(|component| component.display_name())
+                .unwrap_or(component);
+            json!({
+                "msgtype": "m.text",
+                "body": format!("**{}** ({}) status changed to {}", name, page, status),
+                "format": "org.matrix.custom.html",
+                "formatted_body": format!(
+                    "<b>{}</b> ({}) status changed to <font color=\"{}\">{}</font>",
+                    escape_html(name),
+                    escape_html(page),
+                    color,
+                    status
+                ),
+            })
+        }
+    }
+
+    fn escape_html(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for MatrixUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            let txn_id = self.txn_id.fetch_add(1, Ordering::Relaxed);
+            self.client
+                .put(self.build_request_url(txn_id))
+                .bearer_auth(&self.access_token)
+                .json(&self.build_payload(component, page, &status))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+    }
+}
+
+pub use v1::MatrixUpstream;
+
+#[cfg(test)]
+mod tests {
+    use super::MatrixUpstream;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+
+    fn build_upstream() -> MatrixUpstream {
+        let config: Configure = toml::from_str(
+            r#"
+            components = [{ uuid = "00000000000000000000000000000001", name = "Web <1>", identity_id = "component", page = "page" }]
+
+            [statuspage]
+            enabled = false
+            oauth = ""
+
+            [matrix]
+            enabled = true
+            homeserver_url = "https://matrix.org"
+            access_token = "secret token"
+            room_id = "!room:matrix.org"
+
+            [server]
+            addr = "127.0.0.1"
+            port = 41132
+            public_status_page = false
+            "#,
+        )
+        .unwrap();
+        MatrixUpstream::from_configure(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_debug_redacts_access_token() {
+        let output = format!("{:?}", build_upstream());
+        assert!(!output.contains("secret token"));
+        assert!(output.contains("<redacted>"));
+    }
+
+    #[test]
+    fn test_payload_uses_component_name() {
+        let upstream = build_upstream();
+        let payload = upstream.build_payload("component", "page", &ComponentStatus::Operational);
+        assert!(payload["formatted_body"]
+            .as_str()
+            .unwrap()
+            .starts_with("<b>Web &lt;1&gt;</b> (page)"));
+        assert!(payload["body"]
+            .as_str()
+            .unwrap()
+            .starts_with("**Web <1>** (page)"));
+
+        let payload = upstream.build_payload("unknown", "page", &ComponentStatus::Operational);
+        assert!(payload["body"]
+            .as_str()
+            .unwrap()
+            .starts_with("**unknown** (page)"));
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ ** Copyright (C) 2022 KunoiSayami
+ **
+ ** This program is free software: you can redistribute it and/or modify
+ ** it under the terms of the GNU Affero General Public License as published by
+ ** the Free Software Foundation, either version 3 of the License, or
+ ** any later version.
+ **
+ ** This program is distributed in the hope that it will be useful,
+ ** but WITHOUT ANY WARRANTY; without even the implied warranty of
+ ** MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ ** GNU Affero General Public License for more details.
+ **
+ ** You should have received a copy of the GNU Affero General Public License
+ ** along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+mod v2 {
+    use super::EVENTS_URL;
+    use crate::configure::Component;
+    use crate::datastructures::UpstreamTrait;
+    use crate::statuspagelib::ComponentStatus;
+    use crate::Configure;
+    use anyhow::anyhow;
+    use reqwest::Client;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone)]
+    pub struct PagerDutyUpstream {
+        client: Client,
+        routing_key: String,
+        components: HashMap<String, Component>,
+    }
+
+    impl PagerDutyUpstream {
+        pub fn from_configure(cfg: &Configure) -> anyhow::Result<Option<PagerDutyUpstream>> {
+            if !cfg.pagerduty().enabled() {
+                return Ok(None);
+            }
+            if cfg.pagerduty().routing_key().is_empty() {
+                return Err(anyhow!("Routing key field is empty"));
+            }
+            Ok(Some(Self {
+                client: reqwest::ClientBuilder::new()
+                    .timeout(Duration::from_secs(cfg.server().upstream_timeout_secs()))
+                    .build()
+                    .unwrap(),
+                routing_key: cfg.pagerduty().routing_key().to_string(),
+                components: cfg.components_by_upstream_id(),
+            }))
+        }
+
+        pub fn build_payload(
+            &self,
+            component: &str,
+            page: &str,
+            status: &ComponentStatus,
+        ) -> serde_json::Value {
+            let (event_action, severity) = match status {
+                ComponentStatus::Operational => ("resolve", "info"),
+                ComponentStatus::UnderMaintenance => ("acknowledge", "info"),
+                ComponentStatus::DegradedPerformance => ("trigger", "warning"),
+                ComponentStatus::PartialOutage => ("trigger", "error"),
+                ComponentStatus::MajorOutage => ("trigger", "critical"),
+            };
+            // Fallback to upstream id if component is not found in configure
+            let (uuid, name) = match self.components.get(component) {
+                Some(component) => (component.uuid(), component.display_name()),
+                None => (component, component),
+            };
+            json!({
+                "routing_key": self.routing_key,
+                "event_action": event_action,
+                "dedup_key": uuid,
+                "payload": {
+                    "summary": format!("Component {} is {}", name, status),
+                    "source": env!("CARGO_PKG_NAME"),
+                    "severity": severity,
+                    "component": name,
+                    "group": page,
+                }
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for PagerDutyUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            self.client
+                .post(EVENTS_URL)
+                .json(&self.build_payload(component, page, &status))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+    }
+}
+
+pub use v2::PagerDutyUpstream;
@@ -1,59 +1,415 @@
 pub mod v1 {
-    use crate::configure::Component;
-    use crate::database::get_current_timestamp;
-    use crate::datastructures::{ServerLastStatus, TransferData, UpstreamTrait};
-    use axum::extract::Path;
-    use axum::http::StatusCode;
-    use axum::response::{IntoResponse, Response};
+    use crate::configure::{Component, Configure};
+    use crate::database::{
+        audit, get_all_machines, get_current_timestamp, get_machine, MachineRow,
+    };
+    use crate::datastructures::{
+        ComponentItem, PageSummary, ServerLastStatus, StatusEvent, TimelineInterval, TransferData,
+        UpstreamTrait,
+    };
+    use crate::error::StatusUpstreamError;
+    use crate::statuspagelib::ComponentStatus;
+    use askama::Template;
+    use axum::body::{Body, Bytes, HttpBody};
+    use axum::extract::{ConnectInfo, Path, Query, State};
+    use axum::http::header::{
+        ACCEPT, AUTHORIZATION, CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE,
+        REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    };
+    use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode};
+    use axum::middleware::Next;
+    use axum::response::{Html, IntoResponse, Redirect, Response};
     use axum::{Json, Router};
+    use chrono::TimeZone;
     #[cfg(any(feature = "env_logger", feature = "log4rs"))]
-    use log::error;
+    use log::{debug, error, info, trace};
+    use serde_derive::Deserialize;
     use serde_json::json;
     #[cfg(feature = "spdlog-rs")]
     use spdlog::prelude::*;
-    use sqlx::SqliteConnection;
+    use sqlx::SqlitePool;
+    use std::collections::{BTreeMap, HashMap};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
-    use tokio::sync::Mutex;
+    use tokio::sync::{broadcast, RwLock};
     use tower::ServiceBuilder;
+    use tower_http::set_header::SetResponseHeaderLayer;
     use tower_http::trace::TraceLayer;
 
     pub const VERSION: &str = "1";
+    const DEFAULT_AUDIT_LIMIT: u32 = 100;
+    const MAX_LOGGED_BODY_LENGTH: usize = 1024;
+    const MAX_REQUEST_BODY_LENGTH: usize = 64 * 1024;
+    const MAX_AUDIT_LIMIT: u32 = 1000;
+    const DEFAULT_TIMELINE_SECS: u64 = 86400;
+    const GRAPH_HOURS: u64 = 24;
+    const GRAPH_BAR_WIDTH: u64 = 8;
+    const GRAPH_BAR_GAP: u64 = 2;
+    const GRAPH_HEIGHT: u64 = 40;
+    const VERSIONED_MEDIA_TYPE_PREFIX: &str = "application/vnd.status-upstream.v";
+    const VERSIONED_MEDIA_TYPE_SUFFIX: &str = "+json";
+    const SUPPORTED_VERSIONS: [&str; 2] = [VERSION, super::v2::VERSION];
+    const STRICT_TRANSPORT_SECURITY_VALUE: &str = "max-age=31536000; includeSubDomains";
+    const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'none'";
+    // Status page use inline style sheet
+    const STATUS_PAGE_CONTENT_SECURITY_POLICY: &str =
+        "default-src 'none'; style-src 'unsafe-inline'";
     pub type FetchReturnType = (String, Option<String>, Option<String>);
 
-    pub fn make_router(conn: SqliteConnection, upstream: Box<dyn UpstreamTrait>) -> Router {
-        let conn = Arc::new(Mutex::new(conn));
-        let upstream = Arc::new(upstream);
-        Router::new()
+    #[derive(Debug, Deserialize)]
+    pub struct ListQuery {
+        q: Option<String>,
+        tag: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct AuditQuery {
+        uuid: Option<String>,
+        from: Option<u64>,
+        to: Option<u64>,
+        limit: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct AdminComponentsQuery {
+        format: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct TimelineQuery {
+        start: Option<u64>,
+        end: Option<u64>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct ResetQuery {
+        clear_upstream: Option<bool>,
+    }
+
+    pub struct StatusPageComponent {
+        name: String,
+        status: String,
+    }
+
+    #[derive(Template)]
+    #[template(path = "status.html")]
+    pub struct StatusPageTemplate {
+        pages: BTreeMap<String, Vec<StatusPageComponent>>,
+    }
+
+    #[derive(Clone)]
+    pub struct AppState {
+        pool: SqlitePool,
+        upstream: Arc<dyn UpstreamTrait>,
+        events: broadcast::Sender<StatusEvent>,
+        ready: Arc<AtomicBool>,
+        components: Arc<RwLock<HashMap<String, Component>>>,
+        status_aliases: Arc<HashMap<String, String>>,
+        admin_auth_header: Option<Arc<str>>,
+    }
+
+    impl AppState {
+        pub fn new(
+            config: &Configure,
+            pool: SqlitePool,
+            upstream: Arc<dyn UpstreamTrait>,
+            events: broadcast::Sender<StatusEvent>,
+            ready: Arc<AtomicBool>,
+        ) -> Self {
+            Self {
+                pool,
+                upstream,
+                events,
+                ready,
+                components: Arc::new(RwLock::new(component_map(config))),
+                status_aliases: Arc::new(
+                    config
+                        .server()
+                        .status_aliases()
+                        .cloned()
+                        .unwrap_or_default(),
+                ),
+                admin_auth_header: config.server().admin_auth_header().map(Arc::from),
+            }
+        }
+
+        pub async fn update_components(&self, config: &Configure) {
+            *self.components.write().await = component_map(config);
+        }
+
+        fn check_admin_auth(&self, headers: &HeaderMap) -> bool {
+            self.admin_auth_header
+                .as_deref()
+                .is_some_and(|admin_auth_header| check_admin_auth(headers, admin_auth_header))
+        }
+    }
+
+    fn component_map(config: &Configure) -> HashMap<String, Component> {
+        config
+            .components()
+            .iter()
+            .map(|component| (component.uuid().to_string(), component.clone()))
+            .collect()
+    }
+
+    pub fn make_router(config: &Configure, state: AppState, debug: bool) -> Router {
+        let mut router = Router::new()
+            .route("/v1/components", axum::routing::get(list))
             .route(
                 "/v1/components/:component_id",
-                axum::routing::get({
-                    let conn = conn.clone();
-                    |path| async move { get(Path(path), conn).await }
-                })
-                .post({
-                    let conn = conn.clone();
-                    let upstream = upstream.clone();
-                    |path, payload| async move { post(path, payload, upstream, conn).await }
-                }),
+                axum::routing::get(get).post(post),
+            )
+            .route(
+                "/v1/components/:component_id/timeline",
+                axum::routing::get(timeline),
+            )
+            .route(
+                "/v1/components/:component_id/graph.svg",
+                axum::routing::get(uptime_graph),
             )
+            .route("/v1/pages", axum::routing::get(pages))
+            .route("/", axum::routing::get(index))
             .route(
-                "/",
-                axum::routing::get(|| async { Json(json!({ "version": VERSION, "status": 200 })) }),
+                "/robots.txt",
+                axum::routing::get(|| async {
+                    ([(CONTENT_TYPE, "text/plain")], "User-agent: *\nDisallow: /")
+                }),
             )
-            .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
+            .route(
+                "/favicon.ico",
+                axum::routing::get(|| async { StatusCode::NO_CONTENT }),
+            );
+        if state.admin_auth_header.is_some() {
+            router = router
+                .route("/admin/audit", axum::routing::get(audit_log))
+                .route("/admin/components", axum::routing::get(admin_components))
+                .route(
+                    "/v1/components/:component_id/reset",
+                    axum::routing::post(reset),
+                );
+        }
+        router = router.merge(super::v2::routes(state.admin_auth_header.is_some()));
+        if config.server().public_status_page() {
+            router = router.route("/status", axum::routing::get(status_page));
+        }
+        let probes = Router::new()
+            .route("/v1/liveness", axum::routing::get(liveness))
+            .route("/v1/readiness", axum::routing::get(readiness))
+            .merge(super::v2::probes());
+        let router = match config.server().base_path() {
+            Some(base_path) => {
+                let index_path = format!("{}/", base_path);
+                let mut root = Router::new()
+                    .nest(base_path, router.merge(probes.clone()))
+                    .route(&index_path, axum::routing::get(index))
+                    .route(
+                        "/",
+                        axum::routing::get(move || async move { Redirect::temporary(&index_path) }),
+                    );
+                if config.server().base_path_exclude_probes() {
+                    root = root.merge(probes);
+                }
+                root
+            }
+            None => router.merge(probes),
+        };
+        let mut router = router.with_state(state);
+        router = router.layer(axum::middleware::from_fn(check_api_version));
+        if debug {
+            router = router.layer(axum::middleware::from_fn(log_request_body));
+        }
+        let security_headers = config.server().security_headers();
+        if security_headers.enabled() {
+            if security_headers.strict_transport_security() {
+                router = router.layer(SetResponseHeaderLayer::if_not_present(
+                    STRICT_TRANSPORT_SECURITY,
+                    HeaderValue::from_static(STRICT_TRANSPORT_SECURITY_VALUE),
+                ));
+            }
+            router = router.layer(
+                ServiceBuilder::new()
+                    .layer(SetResponseHeaderLayer::if_not_present(
+                        X_CONTENT_TYPE_OPTIONS,
+                        HeaderValue::from_static("nosniff"),
+                    ))
+                    .layer(SetResponseHeaderLayer::if_not_present(
+                        X_FRAME_OPTIONS,
+                        HeaderValue::from_static("DENY"),
+                    ))
+                    .layer(SetResponseHeaderLayer::if_not_present(
+                        CONTENT_SECURITY_POLICY,
+                        HeaderValue::from_static(DEFAULT_CONTENT_SECURITY_POLICY),
+                    ))
+                    .layer(SetResponseHeaderLayer::if_not_present(
+                        REFERRER_POLICY,
+                        HeaderValue::from_static("no-referrer"),
+                    )),
+            );
+        }
+        if config.server().enable_request_logging() {
+            router = router.layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
+        }
+        router
     }
 
-    pub async fn post(
-        Path(uuid): Path<String>,
-        Json(payload): Json<TransferData>,
-        upstream: Arc<Box<dyn UpstreamTrait>>,
-        sql_conn: Arc<Mutex<SqliteConnection>>,
-    ) -> impl IntoResponse {
-        let last_status = ServerLastStatus::try_from(payload.status())
-            .map_err(|e| error!("Got error while read data: {:?}", e));
-
-        let last_status = match last_status {
-            Ok(status) => status,
+    // Read body until `limit`, return None if body is longer
+    async fn read_body_limited(
+        mut body: Body,
+        limit: usize,
+    ) -> Result<Option<Bytes>, hyper::Error> {
+        let mut buffer = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            if buffer.len() + chunk.len() > limit {
+                return Ok(None);
+            }
+            buffer.extend_from_slice(&chunk);
+        }
+        Ok(Some(buffer.into()))
+    }
+
+    pub async fn log_request_body(
+        path: Option<Path<HashMap<String, String>>>,
+        request: Request<Body>,
+        next: Next<Body>,
+    ) -> Response {
+        if request.method() != Method::POST {
+            return next.run(request).await;
+        }
+        let (parts, body) = request.into_parts();
+        let bytes = match read_body_limited(body, MAX_REQUEST_BODY_LENGTH).await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    json!({"status": 413}).to_string(),
+                )
+                    .into_response();
+            }
+            Err(e) => {
+                error!("Got error while reading request body: {:?}", e);
+                return (StatusCode::BAD_REQUEST, json!({"status": 400}).to_string())
+                    .into_response();
+            }
+        };
+        // Body may contain sensitive fields, keep it out of debug log
+        trace!(
+            "Request body of {}: {}",
+            path.as_ref()
+                .and_then(|Path(params)| params.get("component_id"))
+                .map(|uuid| uuid.as_str())
+                .unwrap_or_else(|| parts.uri.path()),
+            String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_LOGGED_BODY_LENGTH)])
+        );
+        next.run(Request::from_parts(parts, Body::from(bytes)))
+            .await
+    }
+
+    pub async fn index() -> Json<serde_json::Value> {
+        Json(json!({
+            "version": VERSION,
+            "latest_version": super::v2::VERSION,
+            "status": 200
+        }))
+    }
+
+    fn requested_api_versions(headers: &HeaderMap) -> Vec<String> {
+        headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|media_type| {
+                media_type
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .strip_prefix(VERSIONED_MEDIA_TYPE_PREFIX)?
+                    .strip_suffix(VERSIONED_MEDIA_TYPE_SUFFIX)
+                    .map(|version| version.to_string())
+            })
+            .collect()
+    }
+
+    pub async fn check_api_version(request: Request<Body>, next: Next<Body>) -> Response {
+        let versions = requested_api_versions(request.headers());
+        if versions.is_empty()
+            || versions
+                .iter()
+                .any(|version| SUPPORTED_VERSIONS.contains(&version.as_str()))
+        {
+            return next.run(request).await;
+        }
+        debug!("Reject unsupported api version(s): {:?}", versions);
+        (
+            StatusCode::NOT_ACCEPTABLE,
+            json!({"error": "unsupported_api_version", "supported": SUPPORTED_VERSIONS})
+                .to_string(),
+        )
+            .into_response()
+    }
+
+    pub async fn liveness() -> Json<serde_json::Value> {
+        Json(json!({ "status": 200 }))
+    }
+
+    pub async fn readiness(State(state): State<AppState>) -> Response {
+        if state.ready.load(Ordering::Relaxed) {
+            let query_result = sqlx::query_as::<_, (i32,)>("SELECT 1")
+                .fetch_one(&state.pool)
+                .await
+                .map_err(|e| error!("Got error while checking database: {:?}", e));
+            if query_result.is_ok() {
+                return (StatusCode::OK, json!({"status": 200}).to_string()).into_response();
+            }
+        }
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({"status": 503}).to_string(),
+        )
+            .into_response()
+    }
+
+    fn component_name(components: &HashMap<String, Component>, uuid: String) -> String {
+        match components.get(&uuid) {
+            Some(component) if !component.name().is_empty() => component.name().to_string(),
+            _ => uuid,
+        }
+    }
+
+    fn parse_tags(tags: &str) -> Vec<String> {
+        serde_json::from_str(tags).unwrap_or_default()
+    }
+
+    fn match_reason(uuid: &str, component: Option<&Component>, query: &str) -> Option<String> {
+        if uuid.to_lowercase().contains(query) {
+            return Some("uuid".to_string());
+        }
+        let component = component?;
+        if component.name().to_lowercase().contains(query) {
+            return Some("name".to_string());
+        }
+        component
+            .tags()
+            .iter()
+            .find(|tag| tag.to_lowercase().contains(query))
+            .map(|tag| format!("tag: {}", tag))
+    }
+
+    pub async fn list(Query(query): Query<ListQuery>, State(state): State<AppState>) -> Response {
+        let query_result = sqlx::query_as::<_, (String, i32, String)>(
+            r#"SELECT "uuid", "status", "tags" FROM "machines"
+            WHERE (? IS NULL OR EXISTS (SELECT 1 FROM json_each("machines"."tags") WHERE json_each.value = ?))"#,
+        )
+        .bind(&query.tag)
+        .bind(&query.tag)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| error!("Got error while fetching all components: {:?}", e));
+        let rows = match query_result {
+            Ok(rows) => rows,
             Err(_) => {
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -63,99 +419,1036 @@ pub mod v1 {
             }
         };
 
-        let mut sql_conn = sql_conn.lock().await;
+        let keyword = query.q.map(|q| q.to_lowercase());
+        let components = state.components.read().await;
+        let mut items = Vec::new();
+        for (uuid, status, tags) in rows {
+            // Row of component removed from configure is kept, but not listed
+            if !components.contains_key(&uuid) {
+                continue;
+            }
+            let match_reason = match keyword {
+                Some(ref keyword) => match match_reason(&uuid, components.get(&uuid), keyword) {
+                    Some(reason) => Some(reason),
+                    None => continue,
+                },
+                None => None,
+            };
+            items.push(ComponentItem::new(
+                uuid.clone(),
+                component_name(&components, uuid),
+                ServerLastStatus::from_db_code(status)
+                    .unwrap_or(ServerLastStatus::Unknown)
+                    .to_string(),
+                parse_tags(&tags),
+                match_reason,
+            ));
+        }
+        Json(items).into_response()
+    }
 
-        let ret = sqlx::query_as::<_, FetchReturnType>(
-            r#"SELECT "uuid", "page", "component_id" FROM "matchines" WHERE "uuid" = ?"#,
+    pub async fn pages(State(state): State<AppState>) -> Response {
+        let query_result = sqlx::query_as::<_, (String, String, i32)>(
+            r#"SELECT "uuid", "page", "status" FROM "machines" WHERE "page" IS NOT NULL"#,
         )
-        .bind(&uuid)
-        .fetch_optional(&mut *sql_conn)
+        .fetch_all(&state.pool)
         .await
-        .map_err(|e| error!("Fetch {} component error: {:?}", &uuid, e))
-        .map(|r| {
-            if r.is_none() {
-                error!("Fetch component {} is null", &uuid)
-            }
-            r
-        });
-
-        let component = match ret {
-            Ok(Some(ret)) => Component::from(ret),
-            _ => {
+        .map_err(|e| error!("Got error while fetching pages: {:?}", e));
+        let rows = match query_result {
+            Ok(rows) => rows,
+            Err(_) => {
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     json!({"status": 500}).to_string(),
                 )
-                    .into_response();
+                    .into_response()
             }
         };
 
-        let query_ret = sqlx::query(
-            r#"UPDATE "machines" SET "status" = ?, "last_update" = ? WHERE "uuid" = ?"#,
+        let components = state.components.read().await;
+        let mut pages: BTreeMap<String, PageSummary> = BTreeMap::new();
+        for (uuid, page, status) in rows {
+            // Row of component removed from configure is kept, but not counted
+            if !components.contains_key(&uuid) {
+                continue;
+            }
+            pages
+                .entry(page.clone())
+                .or_insert_with(|| PageSummary::new(page))
+                .add(
+                    ServerLastStatus::from_db_code(status).unwrap_or(ServerLastStatus::Unknown),
+                    1,
+                );
+        }
+        Json(pages.into_values().collect::<Vec<_>>()).into_response()
+    }
+
+    pub async fn status_page(State(state): State<AppState>) -> Response {
+        let query_result = sqlx::query_as::<_, (String, i32, Option<String>)>(
+            r#"SELECT "uuid", "status", "page" FROM "machines""#,
         )
-        .bind(payload.status())
-        .bind(get_current_timestamp() as u32)
-        .bind(&uuid)
-        .execute(&mut *sql_conn)
+        .fetch_all(&state.pool)
         .await
-        .map_err(|e| {
-            error!(
-                "Update database for {} to {} error: {:?}",
-                &uuid,
-                payload.status(),
-                e
+        .map_err(|e| error!("Got error while fetching all components: {:?}", e));
+        let rows = match query_result {
+            Ok(rows) => rows,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response()
+            }
+        };
+
+        let components = state.components.read().await;
+        let mut pages: BTreeMap<String, Vec<StatusPageComponent>> = BTreeMap::new();
+        for (uuid, status, page) in rows {
+            if !components.contains_key(&uuid) {
+                continue;
+            }
+            pages
+                .entry(page.unwrap_or_default())
+                .or_default()
+                .push(StatusPageComponent {
+                    name: component_name(&components, uuid),
+                    status: ServerLastStatus::from_db_code(status)
+                        .unwrap_or(ServerLastStatus::Unknown)
+                        .to_string(),
+                });
+        }
+
+        match (StatusPageTemplate { pages }).render() {
+            Ok(body) => (
+                [(CONTENT_SECURITY_POLICY, STATUS_PAGE_CONTENT_SECURITY_POLICY)],
+                Html(body),
             )
-        });
+                .into_response(),
+            Err(e) => {
+                error!("Got error while rendering status page: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
 
-        let upstream_ret = upstream
-            .set_component_status(component.report_id(), component.page(), last_status.into())
-            .await
-            .map_err(|e| error!("Got error while upload status to server: {:?}", e));
+    pub fn check_admin_auth(headers: &HeaderMap, admin_auth_header: &str) -> bool {
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value == admin_auth_header)
+    }
 
-        if query_ret.is_ok() && upstream_ret.is_ok() {
-            (StatusCode::OK, json!({"status": 200}).to_string())
-        } else {
-            (
+    pub async fn admin_components(
+        headers: HeaderMap,
+        Query(query): Query<AdminComponentsQuery>,
+        State(state): State<AppState>,
+    ) -> Response {
+        if !state.check_admin_auth(&headers) {
+            return (StatusCode::UNAUTHORIZED, json!({"status": 401}).to_string()).into_response();
+        }
+        let mut sql_conn = match state.pool.acquire().await {
+            Ok(sql_conn) => sql_conn,
+            Err(e) => {
+                error!("Got error while acquiring database connection: {:?}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response();
+            }
+        };
+        let machines = match get_all_machines(&mut sql_conn).await {
+            Ok(machines) => machines,
+            Err(e) => {
+                error!("Got error while fetching all components: {:?}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response();
+            }
+        };
+        match query.format.as_deref() {
+            Some("csv") => {
+                let mut body = format!("{}\n", MachineRow::CSV_HEADER);
+                for machine in machines {
+                    body.push_str(&format!("{}\n", machine));
+                }
+                ([(CONTENT_TYPE, "text/csv")], body).into_response()
+            }
+            _ => Json(machines).into_response(),
+        }
+    }
+
+    pub async fn reset(
+        Path(uuid): Path<String>,
+        ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
+        Query(query): Query<ResetQuery>,
+        State(state): State<AppState>,
+    ) -> Response {
+        if !state.check_admin_auth(&headers) {
+            return (StatusCode::UNAUTHORIZED, json!({"status": 401}).to_string()).into_response();
+        }
+        let mut sql_conn = match state.pool.acquire().await {
+            Ok(sql_conn) => sql_conn,
+            Err(e) => {
+                error!("Got error while acquiring database connection: {:?}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response();
+            }
+        };
+        let machine = match get_machine(&mut sql_conn, &uuid).await {
+            Ok(Some(machine)) => machine,
+            Ok(None) => {
+                return (StatusCode::NOT_FOUND, json!({"status": 404}).to_string()).into_response()
+            }
+            Err(e) => {
+                error!("Fetch {} component error: {:?}", &uuid, e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response();
+            }
+        };
+
+        let old_status = machine.status().to_string();
+        if let Err(e) =
+            sqlx::query(r#"UPDATE "machines" SET "status" = ?, "last_update" = 0 WHERE "uuid" = ?"#)
+                .bind(ServerLastStatus::Unknown.to_db_code())
+                .bind(&uuid)
+                .execute(&mut *sql_conn)
+                .await
+        {
+            error!("Reset component {} error: {:?}", &uuid, e);
+            return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 json!({"status": 500}).to_string(),
             )
+                .into_response();
         }
-        .into_response()
-    }
+        info!("Reset component {} from {}", &uuid, &old_status);
 
-    pub async fn get(Path(uuid): Path<String>, sql_conn: Arc<Mutex<SqliteConnection>>) -> Response {
-        let mut sql_conn = sql_conn.lock().await;
-        let query_result =
-            sqlx::query_as::<_, (String,)>(r#"SELECT "status" FROM "machines" WHERE "uuid" = ? "#)
-                .bind(&uuid)
-                .fetch_optional(&mut *sql_conn)
+        audit::insert(
+            &mut sql_conn,
+            &uuid,
+            &old_status,
+            &ServerLastStatus::Unknown.to_string(),
+            get_current_timestamp(),
+            Some(remote_addr.ip().to_string()),
+            headers
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+        )
+        .await
+        .map_err(|e| error!("Insert audit log for {} error: {:?}", &uuid, e))
+        .ok();
+        drop(sql_conn);
+
+        if query.clear_upstream.unwrap_or(false) {
+            if let Err(e) = state
+                .upstream
+                .set_component_status(
+                    machine.upstream_id(),
+                    machine.page(),
+                    ComponentStatus::Operational,
+                )
                 .await
-                .map_err(|e| {
-                    error!(
-                        "Got error while fetching component {} status: {:?}",
-                        &uuid, e
-                    )
-                });
-        if let Ok(query_result) = query_result {
-            match query_result {
-                None => (
-                    StatusCode::NOT_FOUND,
-                    serde_json::to_string(&TransferData::not_found()).unwrap(),
-                ),
-                Some((result,)) => (
-                    StatusCode::OK,
-                    serde_json::to_string(&TransferData::new(result)).unwrap(),
-                ),
+            {
+                error!("Got error while clear upstream status: {:?}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500, "old_status": old_status}).to_string(),
+                )
+                    .into_response();
+            }
+        }
+
+        (
+            StatusCode::OK,
+            json!({"status": 200, "old_status": old_status}).to_string(),
+        )
+            .into_response()
+    }
+
+    pub async fn audit_log(
+        headers: HeaderMap,
+        Query(query): Query<AuditQuery>,
+        State(state): State<AppState>,
+    ) -> Response {
+        if !state.check_admin_auth(&headers) {
+            return (StatusCode::UNAUTHORIZED, json!({"status": 401}).to_string()).into_response();
+        }
+        let mut sql_conn = match state.pool.acquire().await {
+            Ok(sql_conn) => sql_conn,
+            Err(e) => {
+                error!("Got error while acquiring database connection: {:?}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response();
+            }
+        };
+        match audit::query(
+            &mut sql_conn,
+            query.uuid.as_deref(),
+            query.from,
+            query.to,
+            query
+                .limit
+                .unwrap_or(DEFAULT_AUDIT_LIMIT)
+                .min(MAX_AUDIT_LIMIT),
+        )
+        .await
+        {
+            Ok(entries) => Json(entries).into_response(),
+            Err(e) => {
+                error!("Got error while querying audit log: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"status": 500}).to_string(),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(uuid = %uuid, remote_addr = %remote_addr))]
+    pub async fn post(
+        Path(uuid): Path<String>,
+        ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
+        State(state): State<AppState>,
+        Json(payload): Json<TransferData>,
+    ) -> Result<Response, StatusUpstreamError> {
+        let last_status =
+            ServerLastStatus::parse_with_aliases(payload.status(), &state.status_aliases)
+                .map_err(|e| StatusUpstreamError::BadRequest(e.to_string()))?;
+        if last_status == ServerLastStatus::Unknown {
+            return Err(StatusUpstreamError::BadRequest(format!(
+                "unexpected status: {}",
+                payload.status()
+            )));
+        }
+        let status = last_status.to_string();
+
+        let machine = get_machine(&mut *state.pool.acquire().await?, &uuid)
+            .await?
+            .ok_or(StatusUpstreamError::NotFound)?;
+
+        // Push before saving, so that a retry after upstream error will push again
+        if machine.status() == last_status {
+            debug!("Component {} status not changed, skip upstream", &uuid);
+            if last_status == ServerLastStatus::Optional {
+                state
+                    .upstream
+                    .heartbeat(machine.upstream_id(), machine.page())
+                    .await
+                    .map_err(StatusUpstreamError::Upstream)?;
             }
         } else {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                json!({"status": 500}).to_string(),
+            state
+                .upstream
+                .set_component_status(machine.upstream_id(), machine.page(), last_status.into())
+                .await
+                .map_err(StatusUpstreamError::Upstream)?;
+        }
+
+        let mut sql_conn = state.pool.acquire().await?;
+        let current_time = get_current_timestamp();
+        sqlx::query(r#"UPDATE "machines" SET "status" = ?, "last_update" = ? WHERE "uuid" = ?"#)
+            .bind(last_status.to_db_code())
+            .bind(current_time as u32)
+            .bind(&uuid)
+            .execute(&mut *sql_conn)
+            .await?;
+
+        if machine.status() != last_status {
+            audit::insert(
+                &mut sql_conn,
+                &uuid,
+                &machine.status().to_string(),
+                &status,
+                current_time,
+                Some(remote_addr.ip().to_string()),
+                headers
+                    .get("x-request-id")
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.to_string()),
             )
+            .await
+            .map_err(|e| error!("Insert audit log for {} error: {:?}", &uuid, e))
+            .ok();
         }
-        .into_response()
+        drop(sql_conn);
+
+        // No receiver is not an error, just nobody is subscribing
+        state
+            .events
+            .send(StatusEvent::new(uuid, status, current_time))
+            .ok();
+
+        Ok((StatusCode::OK, json!({"status": 200}).to_string()).into_response())
+    }
+
+    fn status_color(status: ServerLastStatus) -> &'static str {
+        match status {
+            ServerLastStatus::Optional => "#00cc00",
+            ServerLastStatus::DegradedPerformance => "#cccc00",
+            ServerLastStatus::PartialOutage => "#ff8800",
+            ServerLastStatus::Outage => "#cc0000",
+            ServerLastStatus::Unknown => "#888888",
+        }
+    }
+
+    // Return (uptime percentage, dominant status) of each hour in [since, since + GRAPH_HOURS)
+    fn hourly_uptime(
+        since: u64,
+        mut status: ServerLastStatus,
+        changes: &[(u64, ServerLastStatus)],
+    ) -> Vec<(u64, ServerLastStatus)> {
+        let mut changes = changes.iter().peekable();
+        (0..GRAPH_HOURS)
+            .map(|hour| {
+                let start = since + hour * 3600;
+                let end = start + 3600;
+                let mut durations: HashMap<ServerLastStatus, u64> = HashMap::new();
+                let mut cursor = start;
+                while let Some((changed_at, new_status)) =
+                    changes.next_if(|(changed_at, _)| *changed_at < end)
+                {
+                    let changed_at = (*changed_at).max(cursor);
+                    *durations.entry(status).or_default() += changed_at - cursor;
+                    cursor = changed_at;
+                    status = *new_status;
+                }
+                *durations.entry(status).or_default() += end - cursor;
+                let uptime = durations
+                    .get(&ServerLastStatus::Optional)
+                    .map(|duration| duration * 100 / 3600)
+                    .unwrap_or(0);
+                let dominant = durations
+                    .into_iter()
+                    .max_by_key(|(status, duration)| (*duration, status.severity()))
+                    .map(|(status, _)| status)
+                    .unwrap_or(ServerLastStatus::Unknown);
+                (uptime, dominant)
+            })
+            .collect()
+    }
+
+    fn render_uptime_graph(since: u64, bars: &[(u64, ServerLastStatus)]) -> String {
+        let width = GRAPH_HOURS * (GRAPH_BAR_WIDTH + GRAPH_BAR_GAP) - GRAPH_BAR_GAP;
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#,
+            width = width,
+            height = GRAPH_HEIGHT
+        );
+        for (index, (uptime, status)) in bars.iter().enumerate() {
+            let index = index as u64;
+            // Keep a visible bar even if component is totally down in this hour
+            let height = (GRAPH_HEIGHT * uptime / 100).max(2);
+            let hour = chrono::Utc
+                .timestamp_opt((since + index * 3600) as i64, 0)
+                .single()
+                .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default();
+            svg.push_str(&format!(
+                r#"<rect x="{x}" y="{y}" width="{bar_width}" height="{height}" fill="{color}"><title>{hour}: {uptime}% {status}</title></rect>"#,
+                x = index * (GRAPH_BAR_WIDTH + GRAPH_BAR_GAP),
+                y = GRAPH_HEIGHT - height,
+                bar_width = GRAPH_BAR_WIDTH,
+                height = height,
+                color = status_color(*status),
+                hour = hour,
+                uptime = uptime,
+                status = status
+            ));
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    // Return status at `since` and every status change after it, rebuilt from audit log.
+    // Time not covered by audit log (before first entry, or between entries which do not
+    // follow each other) is reported as unknown.
+    async fn load_status_changes(
+        state: &AppState,
+        uuid: &str,
+        since: u64,
+    ) -> Result<(ServerLastStatus, Vec<(u64, ServerLastStatus)>), StatusUpstreamError> {
+        let mut sql_conn = state.pool.acquire().await?;
+        if get_machine(&mut sql_conn, uuid).await?.is_none() {
+            return Err(StatusUpstreamError::NotFound);
+        }
+        let status_before = audit::query_status_before(&mut sql_conn, uuid, since).await?;
+        let changes = audit::query_changes_since(&mut sql_conn, uuid, since).await?;
+        drop(sql_conn);
+
+        let parse =
+            |status: &str| ServerLastStatus::try_from(status).unwrap_or(ServerLastStatus::Unknown);
+        let mut initial_status = status_before
+            .as_deref()
+            .map(parse)
+            .unwrap_or(ServerLastStatus::Unknown);
+        let mut result: Vec<(u64, ServerLastStatus)> = Vec::with_capacity(changes.len());
+        for (changed_at, old_status, new_status) in &changes {
+            let previous = match result.last_mut() {
+                Some((_, status)) => status,
+                None => &mut initial_status,
+            };
+            if *previous != parse(old_status) {
+                *previous = ServerLastStatus::Unknown;
+            }
+            result.push((*changed_at as u64, parse(new_status)));
+        }
+        Ok((initial_status, result))
+    }
+
+    pub async fn timeline(
+        Path(uuid): Path<String>,
+        Query(query): Query<TimelineQuery>,
+        State(state): State<AppState>,
+    ) -> Result<Response, StatusUpstreamError> {
+        let now = get_current_timestamp();
+        let end = query.end.unwrap_or(now).min(now);
+        let start = query
+            .start
+            .unwrap_or_else(|| end.saturating_sub(DEFAULT_TIMELINE_SECS));
+        if start >= end {
+            return Err(StatusUpstreamError::BadRequest(format!(
+                "start {} should be earlier than end {}",
+                start, end
+            )));
+        }
+        let (mut status, changes) = load_status_changes(&state, &uuid, start).await?;
+
+        let mut intervals = Vec::new();
+        let mut cursor = start;
+        for (changed_at, new_status) in changes.into_iter().take_while(|(at, _)| *at < end) {
+            // Merge adjacent intervals with same status, e.g. unknown gaps
+            if changed_at > cursor && new_status != status {
+                intervals.push(TimelineInterval::new(status, cursor, changed_at));
+                cursor = changed_at;
+            }
+            status = new_status;
+        }
+        intervals.push(TimelineInterval::new(status, cursor, end));
+        Ok(Json(intervals).into_response())
+    }
+
+    pub async fn uptime_graph(
+        Path(uuid): Path<String>,
+        State(state): State<AppState>,
+    ) -> Result<Response, StatusUpstreamError> {
+        let since = get_current_timestamp().saturating_sub(GRAPH_HOURS * 3600);
+        let (initial_status, changes) = load_status_changes(&state, &uuid, since).await?;
+        let bars = hourly_uptime(since, initial_status, &changes);
+        Ok((
+            [
+                (CONTENT_TYPE, "image/svg+xml"),
+                (CACHE_CONTROL, "max-age=300"),
+            ],
+            render_uptime_graph(since, &bars),
+        )
+            .into_response())
+    }
+
+    #[tracing::instrument(skip_all, fields(uuid = %uuid))]
+    pub async fn get(
+        Path(uuid): Path<String>,
+        State(state): State<AppState>,
+    ) -> Result<Response, StatusUpstreamError> {
+        let (status, tags) = sqlx::query_as::<_, (i32, String)>(
+            r#"SELECT "status", "tags" FROM "machines" WHERE "uuid" = ? "#,
+        )
+        .bind(&uuid)
+        .fetch_one(&state.pool)
+        .await?;
+        let status = ServerLastStatus::from_db_code(status)?.to_string();
+        Ok(Json(TransferData::new(status).with_tags(parse_tags(&tags))).into_response())
+    }
+}
+
+pub mod v2 {
+    use super::v1::{
+        get, list, liveness, pages, post, readiness, reset, timeline, uptime_graph, AppState,
+    };
+    use axum::Router;
+
+    pub const VERSION: &str = "2";
+
+    // Every v1 component route is also served under v2
+    pub fn routes(admin: bool) -> Router<AppState> {
+        let router = Router::new()
+            .route("/v2/components", axum::routing::get(list))
+            .route(
+                "/v2/components/:component_id",
+                axum::routing::get(get).post(post),
+            )
+            .route(
+                "/v2/components/:component_id/timeline",
+                axum::routing::get(timeline),
+            )
+            .route(
+                "/v2/components/:component_id/graph.svg",
+                axum::routing::get(uptime_graph),
+            )
+            .route("/v2/pages", axum::routing::get(pages));
+        if admin {
+            router.route(
+                "/v2/components/:component_id/reset",
+                axum::routing::post(reset),
+            )
+        } else {
+            router
+        }
+    }
+
+    pub fn probes() -> Router<AppState> {
+        Router::new()
+            .route("/v2/liveness", axum::routing::get(liveness))
+            .route("/v2/readiness", axum::routing::get(readiness))
     }
 }
 
 pub use current::VERSION as CURRENT_VERSION;
 pub use v1 as current;
+
+#[cfg(test)]
+mod tests {
+    use super::v1::{log_request_body, make_router, AppState};
+    use crate::configure::{Component, Configure};
+    use crate::database;
+    use crate::datastructures::{EmptyUpstream, ServerLastStatus, UpstreamTrait};
+    use crate::statuspagelib::ComponentStatus;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+    use axum::http::{Method, Request, StatusCode};
+    use axum::response::Response;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
+    use tower::ServiceExt;
+
+    const UUID: &str = "00000000000000000000000000000001";
+    const UNKNOWN_UUID: &str = "00000000000000000000000000000002";
+    const ADMIN_AUTH_HEADER: &str = "Bearer admin";
+
+    fn build_configure(server: &str) -> Configure {
+        toml::from_str(&format!(
+            r#"
+            components = [{{ uuid = "{}", name = "test" }}]
+
+            [statuspage]
+            enabled = false
+
+            [server]
+            addr = "127.0.0.1"
+            port = 41132
+            public_status_page = false
+            admin_auth_header = "{}"
+            {}
+            "#,
+            UUID, ADMIN_AUTH_HEADER, server
+        ))
+        .unwrap()
+    }
+
+    // Record every status pushed to upstream
+    #[derive(Default)]
+    struct RecordUpstream(std::sync::Mutex<Vec<(String, String, String)>>);
+
+    #[async_trait::async_trait]
+    impl UpstreamTrait for RecordUpstream {
+        async fn get_component_status(&self, _component: &str, _page: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn set_component_status(
+            &self,
+            component: &str,
+            page: &str,
+            status: ComponentStatus,
+        ) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push((
+                component.to_string(),
+                page.to_string(),
+                status.to_string(),
+            ));
+            Ok(())
+        }
+
+        async fn heartbeat(&self, component: &str, page: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push((
+                component.to_string(),
+                page.to_string(),
+                "heartbeat".to_string(),
+            ));
+            Ok(())
+        }
+    }
+
+    async fn build_router(config: &Configure) -> (Router, SqlitePool) {
+        build_router_with_upstream(config, Arc::new(EmptyUpstream::default())).await
+    }
+
+    async fn build_router_with_upstream(
+        config: &Configure,
+        upstream: Arc<dyn UpstreamTrait>,
+    ) -> (Router, SqlitePool) {
+        // Every connection of pool open its own memory database, so keep only one alive
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        database::migrate(&mut conn).await.unwrap();
+        for component in config.components() {
+            database::upsert_component(&mut conn, component)
+                .await
+                .unwrap();
+        }
+        drop(conn);
+        let state = AppState::new(
+            config,
+            pool.clone(),
+            upstream,
+            broadcast::channel(16).0,
+            Arc::new(AtomicBool::new(true)),
+        );
+        (make_router(config, state, false), pool)
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+        payload: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (key, value) in headers {
+            request = request.header(*key, *value);
+        }
+        let mut request = match payload {
+            Some(payload) => request
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(payload.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))));
+        let response: Response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (
+            status,
+            serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_post_updates_database() {
+        let (router, pool) = build_router(&build_configure("")).await;
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            &format!("/v1/components/{}", UUID),
+            &[],
+            Some(json!({"status": "major_outage"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let machine = database::get_machine(&mut pool.acquire().await.unwrap(), UUID)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(machine.status(), ServerLastStatus::Outage);
+    }
+
+    #[tokio::test]
+    async fn test_post_pushes_unmapped_component() {
+        // Component without statuspage page and component id still reach other upstreams
+        let upstream = Arc::new(RecordUpstream::default());
+        let (router, _) = build_router_with_upstream(&build_configure(""), upstream.clone()).await;
+        let uri = format!("/v1/components/{}", UUID);
+        for _ in 0..2 {
+            let (status, _) = send(
+                &router,
+                Method::POST,
+                &uri,
+                &[],
+                Some(json!({"status": "major_outage"})),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(
+            *upstream.0.lock().unwrap(),
+            vec![(UUID.to_string(), String::new(), "major_outage".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_unchanged_operational_sends_heartbeat() {
+        let upstream = Arc::new(RecordUpstream::default());
+        let (router, _) = build_router_with_upstream(&build_configure(""), upstream.clone()).await;
+        let uri = format!("/v1/components/{}", UUID);
+        for _ in 0..2 {
+            let (status, _) = send(
+                &router,
+                Method::POST,
+                &uri,
+                &[],
+                Some(json!({"status": "operational"})),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(
+            *upstream.0.lock().unwrap(),
+            vec![
+                (UUID.to_string(), String::new(), "operational".to_string()),
+                (UUID.to_string(), String::new(), "heartbeat".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_matches_post() {
+        let (router, _) = build_router(&build_configure("")).await;
+        let uri = format!("/v1/components/{}", UUID);
+        let (status, body) = send(&router, Method::GET, &uri, &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "unknown");
+
+        send(
+            &router,
+            Method::POST,
+            &uri,
+            &[],
+            Some(json!({"status": "degraded_performance"})),
+        )
+        .await;
+        let (status, body) = send(&router, Method::GET, &uri, &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "degraded_performance");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_component() {
+        let (router, _) = build_router(&build_configure("")).await;
+        let uri = format!("/v1/components/{}", UNKNOWN_UUID);
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            &uri,
+            &[],
+            Some(json!({"status": "operational"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = send(&router, Method::GET, &uri, &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["status"], "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_admin_auth_rejection() {
+        let (router, _) = build_router(&build_configure("")).await;
+        let (status, _) = send(&router, Method::GET, "/admin/components", &[], None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(
+            &router,
+            Method::GET,
+            "/admin/components",
+            &[(AUTHORIZATION.as_str(), "Bearer wrong")],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = send(
+            &router,
+            Method::GET,
+            "/admin/components",
+            &[(AUTHORIZATION.as_str(), ADMIN_AUTH_HEADER)],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["uuid"], UUID);
+    }
+
+    #[tokio::test]
+    async fn test_pages_skip_removed_component() {
+        let (router, pool) = build_router(&build_configure("")).await;
+        for uuid in [UUID, UNKNOWN_UUID] {
+            database::upsert_component(
+                &mut pool.acquire().await.unwrap(),
+                &Component::new(
+                    uuid.to_string(),
+                    String::new(),
+                    "component".to_string(),
+                    "page".to_string(),
+                ),
+            )
+            .await
+            .unwrap();
+        }
+        let (status, body) = send(&router, Method::GET, "/v1/pages", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["page_id"], "page");
+        assert_eq!(body[0]["component_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_base_path() {
+        let (router, _) = build_router(&build_configure(r#"base_path = "/monitor""#)).await;
+        let (status, body) = send(&router, Method::GET, "/monitor/v1/components", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["uuid"], UUID);
+        let (status, _) = send(&router, Method::GET, "/v1/components", &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&router, Method::GET, "/monitor/v1/liveness", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&router, Method::GET, "/v1/liveness", &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (router, _) = build_router(&build_configure(
+            "base_path = \"/monitor\"\nbase_path_exclude_probes = true",
+        ))
+        .await;
+        let (status, _) = send(&router, Method::GET, "/v1/liveness", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_timeline_fills_unknown() {
+        let (router, pool) = build_router(&build_configure("")).await;
+        let end = database::get_current_timestamp();
+        let start = end - 3000;
+        // Status between these entries is not logged, e.g. changed by database import
+        for (changed_at, old_status, new_status) in [
+            (start + 1000, "operational", "degraded_performance"),
+            (start + 2000, "major_outage", "operational"),
+        ] {
+            database::audit::insert(
+                &mut pool.acquire().await.unwrap(),
+                UUID,
+                old_status,
+                new_status,
+                changed_at,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let (status, body) = send(
+            &router,
+            Method::GET,
+            &format!(
+                "/v1/components/{}/timeline?start={}&end={}",
+                UUID, start, end
+            ),
+            &[],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!([
+                {"status": "unknown", "start_time": start, "end_time": start + 2000},
+                {"status": "operational", "start_time": start + 2000, "end_time": end},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_v2_serves_v1_routes() {
+        let (router, _) = build_router(&build_configure("")).await;
+        for path in [
+            "components".to_string(),
+            format!("components/{}", UUID),
+            format!("components/{}/timeline", UUID),
+            format!("components/{}/graph.svg", UUID),
+            "pages".to_string(),
+            "liveness".to_string(),
+            "readiness".to_string(),
+        ] {
+            for version in ["v1", "v2"] {
+                let uri = format!("/{}/{}", version, path);
+                let (status, _) = send(&router, Method::GET, &uri, &[], None).await;
+                assert_eq!(status, StatusCode::OK, "{}", uri);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_request_body_keeps_body() {
+        let router = Router::new()
+            .route(
+                "/v1/components/:component_id",
+                axum::routing::post(|Json(payload): Json<serde_json::Value>| async move {
+                    Json(payload)
+                }),
+            )
+            .layer(axum::middleware::from_fn(log_request_body));
+        // Longer than logged length, handler should still receive whole body
+        let payload = json!({"status": "operational", "padding": "x".repeat(2048)});
+        let response = router
+            .oneshot(
+                Request::post("/v1/components/uuid")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            payload
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_request_body_limit() {
+        let router = Router::new()
+            .route(
+                "/v1/components/:component_id",
+                axum::routing::post(|| async { StatusCode::OK }),
+            )
+            .layer(axum::middleware::from_fn(log_request_body));
+        let response = router
+            .oneshot(
+                Request::post("/v1/components/uuid")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from("x".repeat(128 * 1024)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}